@@ -15,9 +15,13 @@ use pf_sandbox_lib::package::Package;
 use pf_sandbox_lib::package;
 use crate::ai;
 use crate::cli::{CLIResults, ContinueFrom};
-use crate::game::{Game, GameState, GameSetup, PlayerSetup};
+use crate::game::{Game, GameState, GameSetup, PlayerSetup, Handicap};
+use crate::game;
+use crate::history::PlayerHistory;
 use crate::input::Input;
 use crate::menu::{Menu, MenuState, ResumeMenu};
+use crate::replays::Replay;
+use crate::replays;
 
 use winit::event::Event;
 use winit_input_helper::WinitInputHelper;
@@ -49,8 +53,13 @@ impl OsInput {
 }
 
 pub fn run(mut cli_results: CLIResults, config: Config) {
-    if let ContinueFrom::Close = cli_results.continue_from {
-        return;
+    match cli_results.continue_from {
+        ContinueFrom::Close => { return; }
+        ContinueFrom::Replay | ContinueFrom::VerifyReplays => {
+            run_headless_replays(cli_results, config);
+            return;
+        }
+        _ => { }
     }
 
     let mut context = Context::new().unwrap();
@@ -145,8 +154,9 @@ pub fn run(mut cli_results: CLIResults, config: Config) {
                 for i in 0..input_len {
                     controllers.push(i);
                     players.push(PlayerSetup {
-                        fighter: cli_results.fighter_names[i % cli_results.fighter_names.len()].clone(),
-                        team:    i
+                        fighter:  cli_results.fighter_names[i % cli_results.fighter_names.len()].clone(),
+                        team:     i,
+                        handicap: Handicap::default(),
                     });
                 }
 
@@ -164,8 +174,9 @@ pub fn run(mut cli_results: CLIResults, config: Config) {
                 if let Some(total_players) = cli_results.total_cpu_players {
                     for i in 0..total_players {
                         players.push(PlayerSetup {
-                            fighter: cli_results.fighter_names[(players_len + i) % cli_results.fighter_names.len()].clone(),
-                            team:    players_len + i
+                            fighter:  cli_results.fighter_names[(players_len + i) % cli_results.fighter_names.len()].clone(),
+                            team:     players_len + i,
+                            handicap: Handicap::default(),
                         });
                         controllers.push(input_len + i);
                         ais.push(0);
@@ -177,12 +188,16 @@ pub fn run(mut cli_results: CLIResults, config: Config) {
                 }
 
                 let setup = GameSetup {
-                    init_seed:      GameSetup::gen_seed(),
-                    input_history:  vec!(),
-                    player_history: vec!(),
-                    stage_history:  vec!(),
-                    stage:          cli_results.stage_name.unwrap(),
-                    state:          GameState::Local,
+                    init_seed:          GameSetup::gen_seed(),
+                    input_history:      vec!(),
+                    player_history:     PlayerHistory::default(),
+                    stage_history:      vec!(),
+                    projectile_history: vec!(),
+                    checksums:          vec!(),
+                    stage:              cli_results.stage_name.unwrap(),
+                    state:              GameState::Local,
+                    sudden_death:       None,
+                    snapshot:           None,
                     controllers,
                     players,
                     ais,
@@ -245,7 +260,7 @@ pub fn run(mut cli_results: CLIResults, config: Config) {
                     os_input,
                 )
             }
-            ContinueFrom::Close => unreachable!()
+            ContinueFrom::Replay | ContinueFrom::VerifyReplays | ContinueFrom::Close => unreachable!()
         }
     };
 
@@ -332,3 +347,94 @@ pub fn run(mut cli_results: CLIResults, config: Config) {
         while frame_start.elapsed() < frame_duration { }
     }
 }
+
+/// Plays back replays as fast as possible without graphics, printing the results and any frames that desync.
+/// Exits with a failure code when any replay desyncs, so that engine changes breaking determinism can be caught.
+fn run_headless_replays(cli_results: CLIResults, mut config: Config) {
+    let mut package = if let Some(package) = cli_results.package.or(config.current_package.clone()).and_then(|x| Package::open_or_generate(&x)) {
+        package
+    } else {
+        println!("Could not load selected package");
+        return;
+    };
+
+    let names = match cli_results.replay_name {
+        Some(name) => vec!(name),
+        None       => replays::get_replay_names(&package),
+    };
+
+    let mut context = Context::new().unwrap();
+    let mut input = Input::new(&mut context);
+    let mut failed = 0;
+    for name in &names {
+        println!("Replay: {}", name);
+        match replays::load_replay(name, &package) {
+            Ok(replay) => {
+                let (next_package, next_config, desynced) = run_headless_replay(&mut input, package, config, replay);
+                package = next_package;
+                config = next_config;
+                if desynced {
+                    failed += 1;
+                }
+            }
+            Err(error) => {
+                println!("    Failed to load replay: {}", error);
+                failed += 1;
+            }
+        }
+    }
+
+    println!("{} of {} replays failed", failed, names.len());
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// Returns true if the replay desynced
+fn run_headless_replay(input: &mut Input, package: Package, config: Config, replay: Replay) -> (Package, Config, bool) {
+    let recorded_players = replay.player_history.clone();
+    let mut setup = replay.into_game_setup(false);
+    input.reset_history();
+    input.set_history(std::mem::replace(&mut setup.input_history, vec!()));
+
+    let mut game = Game::new(package, config, setup);
+    let os_input = WinitInputHelper::new();
+    let netplay = Netplay::new();
+    let mut desynced_frames = 0;
+
+    let results = loop {
+        if let Some(recorded_players) = recorded_players.get(game.current_frame) {
+            let mut diff = vec!();
+            for (i, (recorded, simulated)) in recorded_players.iter().zip(game.players.iter()).enumerate() {
+                diff.extend(game::player_diff(recorded, simulated).into_iter().map(|x| format!("Player {}: {}", i, x)));
+            }
+            if diff.len() > 0 {
+                desynced_frames += 1;
+                println!("    Frame {} differs from the recorded player_history", game.current_frame);
+                for line in diff {
+                    println!("        {}", line);
+                }
+            }
+        }
+
+        match game.step(input, &os_input, true, &netplay) {
+            GameState::Quit (ResumeMenu::Results (results)) => break Some(results),
+            GameState::Quit (_) | GameState::Paused        => break None, // the inputs ran out before the game finished
+            _                                               => { }
+        }
+    };
+
+    match results {
+        Some(results) => {
+            for result in &results.player_results {
+                println!("    {}. {} - kills: {} deaths: {}", result.place + 1, result.fighter, result.kills.len(), result.deaths.len());
+            }
+        }
+        None => println!("    The replay ended before the game finished"),
+    }
+
+    let desynced = desynced_frames > 0 || game.desync_frame.is_some();
+    let (package, config) = game.reclaim();
+    input.reset_history();
+    (package, config, desynced)
+}
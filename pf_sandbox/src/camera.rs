@@ -1,5 +1,5 @@
 use pf_sandbox_lib::fighter::Fighter;
-use pf_sandbox_lib::stage::Stage;
+use pf_sandbox_lib::stage::{Stage, Surface};
 use crate::player::Player;
 
 use winit::event::VirtualKeyCode;
@@ -59,7 +59,7 @@ impl Camera {
         }
     }
 
-    pub fn update(&mut self, os_input: &WinitInputHelper<()>, players: &[Player], fighters: &KeyedContextVec<Fighter>, stage: &Stage) {
+    pub fn update(&mut self, os_input: &WinitInputHelper<()>, players: &[Player], fighters: &KeyedContextVec<Fighter>, stage: &Stage, surfaces: &[Surface]) {
         if let CameraState::Auto = self.state {
             if let Some((width, height)) = os_input.resolution() {
                 self.aspect_ratio = width as f32 / height as f32;
@@ -68,7 +68,7 @@ impl Camera {
             // initialise cam_area using only the first player
             let mut player_iter = players.iter();
             let mut cam_area = match player_iter.next() {
-                Some(player) => player.cam_area(&stage.camera, players, fighters, &stage.surfaces),
+                Some(player) => player.cam_area(&stage.camera, players, fighters, surfaces),
                 None => {
                     self.pan = (0.0, 0.0);
                     self.zoom = 100.0;
@@ -78,7 +78,7 @@ impl Camera {
 
             // grow cam_area to cover all other players
             for player in player_iter {
-                let next_area = player.cam_area(&stage.camera, players, fighters, &stage.surfaces);
+                let next_area = player.cam_area(&stage.camera, players, fighters, surfaces);
                 cam_area.x1 = cam_area.x1.min(next_area.left());
                 cam_area.x2 = cam_area.x2.max(next_area.right());
                 cam_area.y1 = cam_area.y1.min(next_area.bot());
@@ -22,6 +22,8 @@ pub fn cli() -> CLIResults {
     opts.optopt("a", "address",        "IP Address of other client to start netplay with", "IP_ADDRESS");
     opts.optopt("n", "netplayplayers", "Search for a netplay game with the specified number of players", "NUM_PLAYERS");
     opts.optopt("r", "netplayregion",  "Search for a netplay game with the specified region", "REGION");
+    opts.optopt("",  "replay",         "Play back the replay with the specified name without graphics and print the results", "NAME");
+    opts.optopt("",  "verify-replays", "Play back every replay of the package without graphics and report any desyncs", "PACKAGE");
     opts.optopt("g", "graphics",       "Graphics backend to use",
         if cfg!(feature = "wgpu_renderer") {
             "[wgpu|none]"
@@ -118,6 +120,18 @@ pub fn cli() -> CLIResults {
         };
     }
 
+    if let Some(replay) = matches.opt_str("replay") {
+        results.replay_name = Some(replay);
+        results.graphics_backend = GraphicsBackendChoice::Headless;
+        results.continue_from = ContinueFrom::Replay;
+    }
+
+    if let Some(package) = matches.opt_str("verify-replays") {
+        results.package = Some(package);
+        results.graphics_backend = GraphicsBackendChoice::Headless;
+        results.continue_from = ContinueFrom::VerifyReplays;
+    }
+
     if let Some(players) = matches.opt_str("n") {
         if let Ok(players) = players.parse() {
             results.netplay_players = Some(players);
@@ -144,6 +158,7 @@ pub struct CLIResults {
     pub total_cpu_players: Option<usize>,
     pub fighter_names:     Vec<String>,
     pub stage_name:        Option<String>,
+    pub replay_name:       Option<String>,
     pub address:           Option<IpAddr>,
     pub continue_from:     ContinueFrom,
     pub netplay_players:   Option<u8>,
@@ -159,6 +174,7 @@ impl CLIResults {
             total_cpu_players: None,
             fighter_names:     vec!(),
             stage_name:        None,
+            replay_name:       None,
             address:           None,
             continue_from:     ContinueFrom::Menu,
             netplay_players:   None,
@@ -172,6 +188,8 @@ pub enum ContinueFrom {
     Netplay,
     MatchMaking,
     Game,
+    Replay,        // play back a single replay headless
+    VerifyReplays, // play back every replay of a package headless
     Close
 }
 
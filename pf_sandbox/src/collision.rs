@@ -1,31 +1,46 @@
 use treeflection::KeyedContextVec;
 
-use pf_sandbox_lib::fighter::{Fighter, HurtBox, HitBox, CollisionBox, CollisionBoxRole, PowerShield};
+use pf_sandbox_lib::fighter::{Fighter, HurtBox, HitBox, CollisionBox, CollisionBoxRole, PowerShield, Action};
+use pf_sandbox_lib::rules::{Rules, Teams};
 use pf_sandbox_lib::stage::Surface;
 use crate::player::Player;
+use crate::projectile::Projectile;
+
+use num_traits::FromPrimitive;
 
 // def - player who was attacked
 // atk - player who attacked
 
-/// returns a list of hit results for each player
-pub fn collision_check(players: &[Player], fighters: &KeyedContextVec<Fighter>, surfaces: &[Surface]) -> Vec<Vec<CollisionResult>> {
+/// returns a list of hit results for each player and a list of hit results for each projectile
+pub fn collision_check(players: &[Player], projectiles: &[Projectile], fighters: &KeyedContextVec<Fighter>, surfaces: &[Surface], rules: &Rules) -> (Vec<Vec<CollisionResult>>, Vec<Vec<ProjectileCollisionResult>>) {
     let mut result: Vec<Vec<CollisionResult>> = vec!();
     for _ in players {
         result.push(vec!());
     }
+    let mut grab_taken: Vec<usize> = vec!(); // defenders grabbed this frame, a player can only be held by one grabber
 
     'player_atk: for (player_atk_i, player_atk) in players.iter().enumerate() {
         let player_atk_xy = player_atk.public_bps_xy(players, fighters, surfaces);
         let fighter_atk = &fighters[player_atk.fighter.as_ref()];
         for (player_def_i, player_def) in players.iter().enumerate() {
             let player_def_xy = player_def.public_bps_xy(players, fighters, surfaces);
-            if player_atk_i != player_def_i && player_atk.hitlist.iter().all(|x| *x != player_def_i) {
+            if player_atk_i != player_def_i && !player_def.is_intangible() && player_atk.hitlist.iter().all(|x| *x != player_def_i) && can_hit(rules, player_atk.team, player_def.team) {
                 let fighter_def = &fighters[player_def.fighter.as_ref()];
 
                 let frame_atk = &player_atk.relative_frame(fighter_atk, surfaces);
                 let frame_def = &player_def.relative_frame(fighter_def, surfaces);
                 let colboxes_atk = frame_atk.get_hitboxes();
 
+                // The held player is always hit by the hitboxes of pummels and throws
+                if player_atk.grabbing == Some(player_def_i) {
+                    if let Some(colbox_atk) = colboxes_atk.first() {
+                        let hitbox_atk = colbox_atk.hitbox_ref();
+                        result[player_atk_i].push(CollisionResult::HitAtk { hitbox: hitbox_atk.clone(), player_def_i, point: player_def_xy });
+                        result[player_def_i].push(CollisionResult::HitDef { hitbox: hitbox_atk.clone(), hurtbox: HurtBox::default(), player_atk_i });
+                    }
+                    continue;
+                }
+
                 'hitbox_atk: for colbox_atk in &colboxes_atk {
                     // TODO: break this out into a seperate function that can be called by the link checking code
                     let hitbox_atk = colbox_atk.hitbox_ref();
@@ -103,13 +118,17 @@ pub fn collision_check(players: &[Player], fighters: &KeyedContextVec<Fighter>,
                     }
                 }
 
-                for colbox_atk in &colboxes_atk {
+                for colbox_atk in &frame_atk.get_colboxes() {
                     match &colbox_atk.role {
                         &CollisionBoxRole::Grab => {
+                            if player_def.is_grabbed() || player_def.grabbing.is_some() || grab_taken.contains(&player_def_i) {
+                                continue;
+                            }
                             for colbox_def in &frame_def.colboxes[..] {
                                 if let ColBoxCollisionResult::Hit (_) = colbox_collision_check(player_atk_xy, colbox_atk, player_def_xy, colbox_def) {
                                     result[player_atk_i].push(CollisionResult::GrabAtk (player_def_i));
                                     result[player_def_i].push(CollisionResult::GrabDef (player_atk_i));
+                                    grab_taken.push(player_def_i);
                                     break 'player_atk;
                                 }
                             }
@@ -123,9 +142,116 @@ pub fn collision_check(players: &[Player], fighters: &KeyedContextVec<Fighter>,
             }
         }
     }
+
+    let projectile_result = projectile_collision_check(players, projectiles, fighters, surfaces, rules, &mut result);
+    (result, projectile_result)
+}
+
+fn projectile_collision_check(players: &[Player], projectiles: &[Projectile], fighters: &KeyedContextVec<Fighter>, surfaces: &[Surface], rules: &Rules, player_result: &mut [Vec<CollisionResult>]) -> Vec<Vec<ProjectileCollisionResult>> {
+    let mut result: Vec<Vec<ProjectileCollisionResult>> = vec!();
+    for _ in projectiles {
+        result.push(vec!());
+    }
+
+    for (projectile_i, projectile) in projectiles.iter().enumerate() {
+        let projectile_def = if let Some(def) = projectile.def(fighters) { def } else { continue };
+        let projectile_xy = (projectile.x, projectile.y);
+        let frame_atk = &projectile.relative_frame(fighters);
+        let colboxes_atk = frame_atk.get_hitboxes();
+
+        'player_def: for (player_def_i, player_def) in players.iter().enumerate() {
+            if player_def_i == projectile.owner || player_def.is_intangible() || projectile.hitlist.iter().any(|x| *x == player_def_i) || !can_hit(rules, projectile.team, player_def.team) {
+                continue;
+            }
+            let player_def_xy = player_def.public_bps_xy(players, fighters, surfaces);
+            let fighter_def = &fighters[player_def.fighter.as_ref()];
+            let frame_def = &player_def.relative_frame(fighter_def, surfaces);
+
+            for colbox_atk in &colboxes_atk {
+                let hitbox_atk = colbox_atk.hitbox_ref();
+
+                // reflect and absorb take priority over everything else
+                for colbox_def in frame_def.get_colboxes() {
+                    if let ColBoxCollisionResult::Hit (_) = colbox_collision_check(projectile_xy, colbox_atk, player_def_xy, colbox_def) {
+                        match &colbox_def.role {
+                            &CollisionBoxRole::Reflect if projectile_def.reflectable => {
+                                result[projectile_i].push(ProjectileCollisionResult::Reflect { player_def_i });
+                                player_result[player_def_i].push(CollisionResult::ReflectDef (hitbox_atk.clone()));
+                                player_result[projectile.owner].push(CollisionResult::ReflectAtk (hitbox_atk.clone()));
+                                break 'player_def;
+                            }
+                            &CollisionBoxRole::Absorb if projectile_def.absorbable => {
+                                result[projectile_i].push(ProjectileCollisionResult::Absorb);
+                                player_result[player_def_i].push(CollisionResult::AbsorbDef (hitbox_atk.clone()));
+                                player_result[projectile.owner].push(CollisionResult::AbsorbAtk (hitbox_atk.clone()));
+                                break 'player_def;
+                            }
+                            _ => { }
+                        }
+                    }
+                }
+
+                if colbox_shield_collision_check(projectile_xy, colbox_atk, player_def_xy, player_def, fighter_def) {
+                    // a power shield within its reflect window reflects the projectile
+                    let reflect_window = fighter_def.power_shield.as_ref().and_then(|x| x.reflect_window);
+                    if let (Some(Action::PowerShield), Some(reflect_window)) = (Action::from_u64(player_def.action), reflect_window) {
+                        if projectile_def.reflectable && reflect_window > player_def.frame as u64 {
+                            result[projectile_i].push(ProjectileCollisionResult::Reflect { player_def_i });
+                            player_result[player_def_i].push(CollisionResult::ReflectDef (hitbox_atk.clone()));
+                            player_result[projectile.owner].push(CollisionResult::ReflectAtk (hitbox_atk.clone()));
+                            break 'player_def;
+                        }
+                    }
+
+                    result[projectile_i].push(ProjectileCollisionResult::Hit { player_def_i });
+                    player_result[player_def_i].push(CollisionResult::ProjectileHitShieldDef {
+                        hitbox:       hitbox_atk.clone(),
+                        power_shield: fighter_def.power_shield.clone(),
+                        player_atk_i: projectile.owner,
+                        action:       projectile.action,
+                        x:            projectile.x,
+                    });
+                    continue 'player_def;
+                }
+
+                for colbox_def in frame_def.get_colboxes() {
+                    if let ColBoxCollisionResult::Hit (_) = colbox_collision_check(projectile_xy, colbox_atk, player_def_xy, colbox_def) {
+                        match &colbox_def.role {
+                            &CollisionBoxRole::Hurt (ref hurtbox) => {
+                                result[projectile_i].push(ProjectileCollisionResult::Hit { player_def_i });
+                                player_result[player_def_i].push(CollisionResult::ProjectileHitDef {
+                                    hitbox:       hitbox_atk.clone(),
+                                    hurtbox:      hurtbox.clone(),
+                                    player_atk_i: projectile.owner,
+                                    action:       projectile.action,
+                                    x:            projectile.x,
+                                    face_right:   projectile.face_right,
+                                });
+                                player_result[projectile.owner].push(CollisionResult::ProjectileHitAtk { action: projectile.action });
+                                continue 'player_def;
+                            }
+                            &CollisionBoxRole::Invincible => {
+                                result[projectile_i].push(ProjectileCollisionResult::Hit { player_def_i });
+                                continue 'player_def;
+                            }
+                            _ => { }
+                        }
+                    }
+                }
+            }
+        }
+    }
     result
 }
 
+/// Players on the same team can only interact when friendly fire is enabled
+fn can_hit(rules: &Rules, team_atk: usize, team_def: usize) -> bool {
+    match rules.teams {
+        Teams::On { friendly_fire, .. } => friendly_fire || team_atk != team_def,
+        Teams::Off                  => true,
+    }
+}
+
 fn colbox_collision_check(player1_xy: (f32, f32), colbox1: &CollisionBox,  player2_xy: (f32, f32), colbox2: &CollisionBox) -> ColBoxCollisionResult {
     let x1 = player1_xy.0 + colbox1.point.0;
     let y1 = player1_xy.1 + colbox1.point.1;
@@ -186,7 +312,10 @@ pub enum CollisionResult {
     HitAtk       { hitbox: HitBox, player_def_i: usize, point: (f32, f32) },
     HitShieldAtk { hitbox: HitBox, power_shield: Option<PowerShield>, player_def_i: usize },
     HitShieldDef { hitbox: HitBox, power_shield: Option<PowerShield>, player_atk_i: usize },
-    ReflectDef   (HitBox), // TODO: add further details required for recreating projectile
+    ProjectileHitDef       { hitbox: HitBox, hurtbox: HurtBox, player_atk_i: usize, action: u64, x: f32, face_right: bool },
+    ProjectileHitShieldDef { hitbox: HitBox, power_shield: Option<PowerShield>, player_atk_i: usize, action: u64, x: f32 },
+    ProjectileHitAtk       { action: u64 }, // action is the action the projectile was fired from
+    ReflectDef   (HitBox),
     ReflectAtk   (HitBox),
     AbsorbDef    (HitBox),
     AbsorbAtk    (HitBox),
@@ -195,6 +324,12 @@ pub enum CollisionResult {
     Clang        { rebound: bool },
 }
 
+pub enum ProjectileCollisionResult {
+    Hit     { player_def_i: usize }, // includes hitting a shield or an invincible colbox
+    Reflect { player_def_i: usize },
+    Absorb,
+}
+
 // Thoughts on special cases
 // *    when one hitbox connects to multiple hurtboxes HitDef is sent to all defenders
 // *    when one hurtbox is hit by multiple hitboxes it receives HitDef from all attackers
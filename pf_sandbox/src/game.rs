@@ -1,39 +1,66 @@
 use crate::camera::Camera;
 use crate::collision::collision_check;
 use crate::graphics::{GraphicsMessage, Render, RenderType};
+use crate::history::{PlayerHistory, Timeline, TimelineEvent};
 use crate::input::Input;
 use crate::menu::ResumeMenu;
 use crate::player::{Player, RenderPlayer, DebugPlayer, StepContext};
-use crate::replays::Replay;
+use crate::projectile::{Projectile, RenderProjectile};
+use crate::replays::{Replay, PackageSnapshot};
 use crate::replays;
-use crate::results::{GameResults, RawPlayerResult, PlayerResult};
+use crate::results::{GameResults, RawPlayerResult, PlayerResult, DeathRecord};
 
 use pf_sandbox_lib::command_line::CommandLine;
 use pf_sandbox_lib::config::Config;
 use pf_sandbox_lib::fighter::{ActionFrame, CollisionBox, LinkType, Action};
 use pf_sandbox_lib::geometry::Rect;
+use pf_sandbox_lib::history::{Delta, RecentFrames};
 use pf_sandbox_lib::input::{PlayerInput, ControllerInput};
 use pf_sandbox_lib::network::Netplay;
 use pf_sandbox_lib::package::Package;
-use pf_sandbox_lib::rules::Goal;
-use pf_sandbox_lib::stage::{Stage, DebugStage, SpawnPoint, Surface, Floor};
+use pf_sandbox_lib::rules::{Goal, Teams, TeamStocks};
+use pf_sandbox_lib::stage::{Stage, DebugStage, SpawnPoint, Surface, SurfaceMotion, PathPoint, Floor};
 
 use rand_chacha::rand_core::SeedableRng;
 use rand_chacha::ChaChaRng;
+use std::borrow::Cow;
 use std::cmp::Ordering;
 use std::collections::HashSet;
 use std::fmt;
+use std::hash::Hasher;
 use std::time::Duration;
 use chrono::Local;
-use num_traits::{FromPrimitive, ToPrimitive};
+use num_traits::FromPrimitive;
+use serde_json::Value;
 
-use treeflection::{Node, NodeRunner, NodeToken};
+use treeflection::{Node, NodeRunner, NodeToken, ContextVec};
 use winit::event::VirtualKeyCode;
 use winit_input_helper::WinitInputHelper;
 use byteorder::{LittleEndian, WriteBytesExt};
 
+/// The most players that can take part in a single match, three GC adapters worth of ports
+pub const MAX_PLAYERS: usize = 12;
+
+/// Keys used to select a player in the editor, holding control selects from the next bank of players.
+/// Two banks cover every player up to MAX_PLAYERS.
+const PLAYER_EDIT_KEYS: [VirtualKeyCode; 9] = [
+    VirtualKeyCode::Key1,
+    VirtualKeyCode::Key2,
+    VirtualKeyCode::Key3,
+    VirtualKeyCode::Key4,
+    VirtualKeyCode::Key5,
+    VirtualKeyCode::Key6,
+    VirtualKeyCode::Key7,
+    VirtualKeyCode::Key8,
+    VirtualKeyCode::Key9,
+];
+
+/// Area of the replay timeline, in screen coordinates ranging from -1.0 to 1.0
+pub const TIMELINE_AREA: Rect = Rect { x1: -0.9, y1: 0.84, x2: 0.9, y2: 0.87 };
+
 #[NodeActions(
     NodeAction(function="save_replay", return_string),
+    NodeAction(function="jump_frame", return_string),
     NodeAction(function="reset_deadzones", return_string),
     NodeAction(function="copy_stage_to_package", return_string),
     NodeAction(function="copy_package_to_stage", return_string),
@@ -44,17 +71,26 @@ pub struct Game {
     pub config:                 Config,
     pub init_seed:              u64,
     pub state:                  GameState,
-    pub player_history:         Vec<Vec<Player>>,
+    pub player_history:         PlayerHistory,
+    #[serde(skip)]
+    recent_players:             RecentFrames<Player>, // the frames of player_history before current_frame that are rendered behind the players
     pub stage_history:          Vec<Stage>,
+    pub projectile_history:     Vec<Vec<Projectile>>,
+    pub checksums:              Vec<u64>, // checksum of the simulation state on each frame, used to detect desyncs
+    stage_checksum:             u64, // the stage is only changed by the editor, so it is hashed once when the game starts
+    pub desync_frame:           Option<usize>, // first frame a replay did not match its recorded checksum
     pub current_frame:          usize,
     pub saved_frame:            usize,
+    pub timeline:               Timeline,
     pub stage:                  Stage,
     pub players:                Vec<Player>,
+    pub projectiles:            Vec<Projectile>,
     pub debug_stage:            DebugStage,
     pub debug_players:          Vec<DebugPlayer>,
     pub selected_controllers:   Vec<usize>,
     pub selected_ais:           Vec<usize>,
     pub selected_stage:         String,
+    pub sudden_death:           Option<SuddenDeathRound>,
     pub edit:                   Edit,
     pub debug_output_this_step: bool,
     pub debug_lines:            Vec<String>,
@@ -63,7 +99,11 @@ pub struct Game {
     pub camera:                 Camera,
     pub tas:                    Vec<ControllerInput>,
     save_replay:                bool,
+    jump_frame:                 bool,
+    scrubbing:                  bool, // the timeline was clicked and the mouse is still held
     reset_deadzones:            bool,
+    replaced_package:           Option<Package>, // the users package, while the game runs on a replays package snapshot
+    pub snapshot:               PackageSnapshot, // the package data the game started with, saved in replays
 }
 
 /// Frame 0 refers to the initial state of the game.
@@ -72,8 +112,20 @@ pub struct Game {
 /// All previous frame state is used to calculate the next frame, then the current_frame is incremented.
 
 impl Game {
-    pub fn new(package: Package, config: Config, setup: GameSetup) -> Game {
+    pub fn new(package: Package, config: Config, mut setup: GameSetup) -> Game {
+        // replays can be played back with the package data they were recorded with
+        let (package, replaced_package, snapshot) = if let Some(snapshot) = setup.snapshot.take() {
+            let mut snapshot_package = package.clone();
+            snapshot.apply(&mut snapshot_package);
+            (snapshot_package, Some(package), snapshot)
+        } else {
+            let snapshot = PackageSnapshot::new(&package, &setup);
+            (package, None, snapshot)
+        };
+
         let stage = package.stages[setup.stage.as_ref()].clone();
+        let mut stage_hasher = Fnv1a::default();
+        stage_hasher.write(&serde_json::to_vec(&stage).unwrap());
 
         // generate players
         let mut players:       Vec<Player>      = vec!();
@@ -83,7 +135,7 @@ impl Game {
                 // Stage can have less spawn points then players
                 let fighter = player.fighter.clone();
                 let team = player.team;
-                players.push(Player::new(fighter, team, i, &stage, &package));
+                players.push(Player::new(fighter, team, player.handicap.clone(), i, &stage, &package));
                 debug_players.push(Default::default());
             }
         }
@@ -94,16 +146,24 @@ impl Game {
             init_seed:              setup.init_seed,
             state:                  setup.state,
             player_history:         setup.player_history,
+            recent_players:         RecentFrames::new(10),
             stage_history:          setup.stage_history,
+            projectile_history:     setup.projectile_history,
+            checksums:              setup.checksums,
+            stage_checksum:         stage_hasher.finish(),
+            desync_frame:           None,
             current_frame:          0,
             saved_frame:            0,
+            timeline:               Default::default(),
             stage:                  stage,
             players:                players,
+            projectiles:            vec!(),
             debug_stage:            Default::default(),
             debug_players:          debug_players,
             selected_controllers:   setup.controllers,
             selected_ais:           setup.ais,
             selected_stage:         setup.stage,
+            sudden_death:           setup.sudden_death,
             edit:                   Edit::Stage,
             debug_output_this_step: false,
             debug_lines:            vec!(),
@@ -112,7 +172,11 @@ impl Game {
             camera:                 Camera::new(),
             tas:                    vec!(),
             save_replay:            false,
+            jump_frame:             false,
+            scrubbing:              false,
             reset_deadzones:        false,
+            replaced_package,
+            snapshot,
         }
     }
 
@@ -123,10 +187,26 @@ impl Game {
         }
 
         if self.save_replay {
-            replays::save_replay(&Replay::new(self, input), &self.package);
+            replays::save_replay(&Replay::new(self, input), &self.package, &self.config);
             self.save_replay = false;
         }
 
+        if self.jump_frame {
+            self.jump_frame = false;
+            if let GameState::Netplay = self.state {
+                println!("Cannot jump to a frame during netplay");
+            } else {
+                let frame = self.saved_frame;
+                self.jump_to_frame(frame, input, netplay);
+                self.state = GameState::Paused;
+                if self.current_frame == frame {
+                    println!("Jumped to frame {}", frame);
+                } else {
+                    println!("Jumped to frame {}, frame {} is past the end of the input history", self.current_frame, frame);
+                }
+            }
+        }
+
         {
             let state = self.state.clone();
             match state {
@@ -141,7 +221,28 @@ impl Game {
                 GameState::Quit (_)              => { unreachable!(); }
             }
 
-            if !os_input_blocked {
+            if self.timeline_visible() {
+                self.timeline.update(&self.player_history, self.current_frame.max(input.history_len()));
+            }
+
+            if os_input_blocked || !os_input.mouse_held(0) {
+                self.scrubbing = false;
+            }
+            else if os_input.mouse_pressed(0) && self.mouse_over_timeline(os_input) {
+                self.scrubbing = true;
+            }
+
+            if self.scrubbing {
+                if let Some(frame) = self.timeline_frame_at_mouse(os_input) {
+                    if frame != self.current_frame {
+                        self.jump_to_frame(frame, input, netplay);
+                    }
+                }
+                if let GameState::ReplayForwards | GameState::ReplayBackwards = self.state {
+                    self.state = GameState::Paused;
+                }
+            }
+            else if !os_input_blocked {
                 match state {
                     GameState::Local           => { self.step_local_os_input(os_input); }
                     GameState::ReplayForwards  => { self.step_replay_forwards_os_input(os_input); }
@@ -154,7 +255,9 @@ impl Game {
                 }
                 self.camera.update_os_input(os_input);
             }
-            self.camera.update(os_input, &self.players, &self.package.fighters, &self.stage);
+            let surfaces = self.stage.surfaces_at(self.current_frame);
+            self.camera.update(os_input, &self.players, &self.package.fighters, &self.stage, &surfaces);
+            self.recent_players.update(&self.player_history, self.current_frame);
 
             self.generate_debug(input, netplay);
         }
@@ -170,6 +273,16 @@ impl Game {
         String::from("Save replay completed")
     }
 
+    /// Jump to saved_frame, which can be set from the command line to reach any frame.
+    /// The jump happens on the next step, where the frame reached is printed.
+    pub fn jump_frame(&mut self) -> String {
+        if let GameState::Netplay = self.state {
+            return String::from("Cannot jump to a frame during netplay");
+        }
+        self.jump_frame = true;
+        format!("Jumping to frame {}", self.saved_frame)
+    }
+
     pub fn reset_deadzones(&mut self) -> String {
         self.reset_deadzones = true;
         String::from("Deadzones reset")
@@ -232,17 +345,26 @@ impl Game {
     }
 
     fn step_local(&mut self, input: &mut Input, netplay: &Netplay) {
-        self.player_history.push(self.players.clone());
+        self.player_history.push(&self.players);
         self.stage_history.push(self.stage.clone());
+        self.projectile_history.push(self.projectiles.clone());
+        self.checksums.truncate(self.current_frame);
+        self.checksums.push(self.checksum(self.current_frame));
         self.current_frame += 1;
 
         // erase any future history
-        for _ in self.current_frame..self.player_history.len() {
-            self.player_history.pop();
-        }
+        self.player_history.truncate(self.current_frame);
+        self.recent_players.truncate(self.current_frame);
+        self.timeline.truncate(self.current_frame);
         for _ in self.current_frame..self.stage_history.len() {
             self.stage_history.pop();
         }
+        for _ in self.current_frame..self.projectile_history.len() {
+            self.projectile_history.pop();
+        }
+        if self.sudden_death.as_ref().map_or(false, |x| x.frame >= self.current_frame) {
+            self.sudden_death = None;
+        }
 
         // run game loop
         input.game_update(self.current_frame);
@@ -265,25 +387,41 @@ impl Game {
         if !netplay.skip_frame() {
             self.current_frame += 1;
 
-            let start = self.current_frame - netplay.frames_to_step();
+            // frame 0 is the initial state, no inputs are applied to it so it is never simulated again
             let end = self.current_frame;
+            let start = if self.player_history.is_empty() { 0 } else { (end - netplay.frames_to_step()).max(1) };
 
             self.player_history.truncate(start);
+            self.recent_players.truncate(start);
             self.stage_history.truncate(start);
+            self.projectile_history.truncate(start);
+            self.checksums.truncate(start);
+            self.timeline.truncate(start);
+            if self.sudden_death.as_ref().map_or(false, |x| x.frame >= start) {
+                self.sudden_death = None;
+            }
             if start != 0 {
-                self.players = self.player_history.get(start-1).unwrap().clone();
-                self.stage   = self.stage_history.get(start-1).unwrap().clone();
+                self.players     = self.player_history.get(start-1).unwrap();
+                self.stage       = self.stage_history.get(start-1).unwrap().clone();
+                self.projectiles = self.projectile_history.get(start-1).unwrap().clone();
             }
 
             input.netplay_update();
 
             for frame in start..end {
-                let player_inputs = &input.players(frame, netplay);
-                self.step_game(input, player_inputs);
+                // step each frame with its own seed, the same as step_local, so peers agree no matter how far they rolled back
+                self.current_frame = frame;
+                if frame != 0 {
+                    let player_inputs = &input.players(frame, netplay);
+                    self.step_game(input, player_inputs);
+                }
 
-                self.player_history.push(self.players.clone());
+                self.player_history.push(&self.players);
                 self.stage_history.push(self.stage.clone());
+                self.projectile_history.push(self.projectiles.clone());
+                self.checksums.push(self.checksum(frame));
             }
+            self.current_frame = end;
         }
     }
 
@@ -303,41 +441,18 @@ impl Game {
         if os_input.key_pressed(VirtualKeyCode::Key0) {
             self.edit = Edit::Stage;
         }
-        else if os_input.key_pressed(VirtualKeyCode::Key1) && players_len >= 1 {
-            if os_input.held_shift() {
-                self.edit = Edit::Player (0);
-            }
-            else {
-                self.edit = Edit::Fighter (0);
-            }
-            self.update_frame();
-        }
-        else if os_input.key_pressed(VirtualKeyCode::Key2) && players_len >= 2 {
-            if os_input.held_shift() {
-                self.edit = Edit::Player (1);
-            }
-            else {
-                self.edit = Edit::Fighter (1);
-            }
-            self.update_frame();
-        }
-        else if os_input.key_pressed(VirtualKeyCode::Key3) && players_len >= 3 {
-            if os_input.held_shift() {
-                self.edit = Edit::Player (2);
-            }
-            else {
-                self.edit = Edit::Fighter (2);
-            }
-            self.update_frame();
-        }
-        else if os_input.key_pressed(VirtualKeyCode::Key4) && players_len >= 4 {
-            if os_input.held_shift() {
-                self.edit = Edit::Player (3);
-            }
-            else {
-                self.edit = Edit::Fighter (3);
+        else if let Some(key_i) = PLAYER_EDIT_KEYS.iter().position(|key| os_input.key_pressed(*key)) {
+            let bank = if os_input.held_control() { PLAYER_EDIT_KEYS.len() } else { 0 };
+            let player_i = bank + key_i;
+            if player_i < players_len {
+                if os_input.held_shift() {
+                    self.edit = Edit::Player (player_i);
+                }
+                else {
+                    self.edit = Edit::Fighter (player_i);
+                }
+                self.update_frame();
             }
-            self.update_frame();
         }
 
         // game flow control
@@ -360,7 +475,8 @@ impl Game {
             self.saved_frame = self.current_frame;
         }
         else if os_input.key_pressed(VirtualKeyCode::I) {
-            //self.jump_frame(); // TODO: Fix
+            let frame = self.saved_frame;
+            self.jump_to_frame(frame, input, netplay);
         }
         else if os_input.key_pressed(VirtualKeyCode::Return) {
             self.state = GameState::Local;
@@ -388,7 +504,7 @@ impl Game {
                 if self.selector.moving {
                     // undo the operations used to render the player
                     let (raw_d_x, raw_d_y) = os_input.game_mouse_diff(self.camera.for_winit_helper());
-                    let angle = -self.players[player].angle(&self.package.fighters[fighter], &self.stage.surfaces); // rotate by the inverse of the angle
+                    let angle = -self.players[player].angle(&self.package.fighters[fighter], &self.surfaces()); // rotate by the inverse of the angle
                     let d_x = raw_d_x * angle.cos() - raw_d_y * angle.sin();
                     let d_y = raw_d_x * angle.sin() + raw_d_y * angle.cos();
                     let distance = (self.players[player].relative_f(d_x), d_y); // *= -1 is its own inverse
@@ -462,7 +578,7 @@ impl Game {
                         if let Some((m_x, m_y)) = os_input.game_mouse(self.camera.for_winit_helper()) {
                             let selected = {
                                 let player = &self.players[player];
-                                let (p_x, p_y) = player.public_bps_xy(&self.players, &self.package.fighters, &self.stage.surfaces);
+                                let (p_x, p_y) = player.public_bps_xy(&self.players, &self.package.fighters, &self.surfaces());
 
                                 let point = (player.relative_f(m_x - p_x), m_y - p_y);
                                 let new_colbox = CollisionBox::new(point);
@@ -506,7 +622,7 @@ impl Game {
                     if os_input.key_pressed(VirtualKeyCode::Q) {
                         if let Some((m_x, m_y)) = os_input.game_mouse(self.camera.for_winit_helper()) {
                             let player = &self.players[player];
-                            let (p_x, p_y) = player.public_bps_xy(&self.players, &self.package.fighters, &self.stage.surfaces);
+                            let (p_x, p_y) = player.public_bps_xy(&self.players, &self.package.fighters, &self.surfaces());
 
                             let x = player.relative_f(m_x - p_x);
                             let y = m_y - p_y;
@@ -516,8 +632,8 @@ impl Game {
 
                     // handle single selection
                     if let Some((m_x, m_y)) = self.selector.step_single_selection(os_input, &self.camera) {
-                        let (player_x, player_y) = self.players[player].public_bps_xy(&self.players, &self.package.fighters, &self.stage.surfaces);
-                        let frame = self.players[player].relative_frame(&self.package.fighters[fighter], &self.stage.surfaces);
+                        let (player_x, player_y) = self.players[player].public_bps_xy(&self.players, &self.package.fighters, &self.surfaces());
+                        let frame = self.players[player].relative_frame(&self.package.fighters[fighter], &self.surfaces());
 
                         for (i, colbox) in frame.colboxes.iter().enumerate() {
                             let hit_x = colbox.point.0 + player_x;
@@ -546,8 +662,8 @@ impl Game {
 
                     // handle multiple selection
                     if let Some(rect) = self.selector.step_multiple_selection(os_input, &self.camera) {
-                        let (player_x, player_y) = self.players[player].public_bps_xy(&self.players, &self.package.fighters, &self.stage.surfaces);
-                        let frame = self.players[player].relative_frame(&self.package.fighters[fighter], &self.stage.surfaces);
+                        let (player_x, player_y) = self.players[player].public_bps_xy(&self.players, &self.package.fighters, &self.surfaces());
+                        let frame = self.players[player].relative_frame(&self.package.fighters[fighter], &self.surfaces());
 
                         for (i, colbox) in frame.colboxes.iter().enumerate() {
                             let hit_x = colbox.point.0 + player_x;
@@ -631,10 +747,11 @@ impl Game {
                         surfaces_to_delete.reverse();
                         let players = self.players.clone();
                         for surface_i in surfaces_to_delete {
+                            let surfaces = self.stage.surfaces_at(self.current_frame);
                             for player in self.players.iter_mut() {
-                                player.platform_deleted(&players, &self.package.fighters, &self.stage.surfaces, surface_i);
+                                player.platform_deleted(&players, &self.package.fighters, &surfaces, surface_i);
                             }
-                            self.stage.surfaces.remove(surface_i);
+                            self.stage.delete_surface(surface_i);
                         }
 
                         self.update_frame();
@@ -655,12 +772,12 @@ impl Game {
                     }
                     // add stage surface
                     if os_input.key_pressed(VirtualKeyCode::R) {
-                        let surface = Surface { floor: Some(Floor { traction: 1.0, pass_through: false }), .. Surface::default() };
+                        let surface = Surface { floor: Some(Floor { pass_through: false, .. Floor::default() }), .. Surface::default() };
                         self.add_surface(surface, os_input);
                     }
                     // add platform surface
                     if os_input.key_pressed(VirtualKeyCode::F) {
-                        let surface = Surface { floor: Some(Floor { traction: 1.0, pass_through: true }), .. Surface::default() };
+                        let surface = Surface { floor: Some(Floor { pass_through: true, .. Floor::default() }), .. Surface::default() };
                         self.add_surface(surface, os_input);
                     }
                     // add spawn point
@@ -677,6 +794,80 @@ impl Game {
                             self.update_frame();
                         }
                     }
+                    // toggle slippery on the selected floors
+                    if os_input.key_pressed(VirtualKeyCode::T) {
+                        for i in self.selector.surfaces_vec() {
+                            if let Some(ref mut floor) = self.stage.surfaces[i].floor {
+                                floor.slippery = !floor.slippery;
+                            }
+                        }
+                    }
+                    // change conveyor velocity of the selected floors
+                    if os_input.key_pressed(VirtualKeyCode::LBracket) {
+                        for i in self.selector.surfaces_vec() {
+                            if let Some(ref mut floor) = self.stage.surfaces[i].floor {
+                                floor.conveyor -= 0.1;
+                            }
+                        }
+                    }
+                    if os_input.key_pressed(VirtualKeyCode::RBracket) {
+                        for i in self.selector.surfaces_vec() {
+                            if let Some(ref mut floor) = self.stage.surfaces[i].floor {
+                                floor.conveyor += 0.1;
+                            }
+                        }
+                    }
+                    // change rotation speed of the selected surfaces
+                    if os_input.key_pressed(VirtualKeyCode::Comma) {
+                        for i in self.selector.surfaces_vec() {
+                            self.stage.surfaces[i].motion.get_or_insert_with(SurfaceMotion::default).rotation_speed -= 0.005;
+                        }
+                    }
+                    if os_input.key_pressed(VirtualKeyCode::Period) {
+                        for i in self.selector.surfaces_vec() {
+                            self.stage.surfaces[i].motion.get_or_insert_with(SurfaceMotion::default).rotation_speed += 0.005;
+                        }
+                    }
+                    // change how long the selected surfaces are visible for, or hidden for while holding shift
+                    if os_input.key_pressed(VirtualKeyCode::N) {
+                        for i in self.selector.surfaces_vec() {
+                            let motion = self.stage.surfaces[i].motion.get_or_insert_with(SurfaceMotion::default);
+                            if os_input.held_shift() {
+                                motion.hidden_frames = motion.hidden_frames.saturating_sub(10);
+                            } else {
+                                motion.visible_frames = motion.visible_frames.saturating_sub(10);
+                            }
+                        }
+                    }
+                    if os_input.key_pressed(VirtualKeyCode::M) {
+                        for i in self.selector.surfaces_vec() {
+                            let motion = self.stage.surfaces[i].motion.get_or_insert_with(SurfaceMotion::default);
+                            if os_input.held_shift() {
+                                motion.hidden_frames += 10;
+                            } else {
+                                motion.visible_frames += 10;
+                            }
+                        }
+                    }
+                    // add path point to the selected surfaces
+                    if os_input.key_pressed(VirtualKeyCode::P) {
+                        if let Some((m_x, m_y)) = os_input.game_mouse(self.camera.for_winit_helper()) {
+                            for i in self.selector.surfaces_vec() {
+                                let surface = &mut self.stage.surfaces[i];
+                                let (c_x, c_y) = surface.center();
+                                let point = PathPoint::new(m_x - c_x, m_y - c_y);
+                                if let Some(ref mut motion) = surface.motion {
+                                    motion.path.push(point);
+                                } else {
+                                    surface.motion = Some(SurfaceMotion {
+                                        path: ContextVec::from_vec(vec!(point)),
+                                        .. SurfaceMotion::default()
+                                    });
+                                }
+                            }
+                            self.update_frame();
+                        }
+                    }
                     if os_input.key_pressed(VirtualKeyCode::S) {
                         let mut join = false;
                         let mut points: Vec<(f32, f32)> = vec!();
@@ -866,6 +1057,14 @@ impl Game {
     // TODO: Activate by shift+K/L
     fn step_replay_forwards(&mut self, input: &mut Input, netplay: &Netplay) { // TODO: rename: step_replay_forwards_from_input
         if self.current_frame <= input.last_frame() {
+            self.check_desync();
+
+            // input only replays are re-simulated, so the history is recorded as we go
+            if self.player_history.len() == self.current_frame {
+                self.player_history.push(&self.players);
+                self.stage_history.push(self.stage.clone());
+                self.projectile_history.push(self.projectiles.clone());
+            }
             self.current_frame += 1;
             let player_inputs = &input.players(self.current_frame, netplay);
             self.step_game(input, player_inputs);
@@ -895,12 +1094,41 @@ impl Game {
         }
     }
 
+    /// Compares the current state against the checksum recorded for this frame, reporting the first frame that differs.
+    /// When the replay includes the recorded players, the fields that differ are listed.
+    fn check_desync(&mut self) {
+        let checksum = self.checksum(self.current_frame);
+        match self.checksums.get(self.current_frame).cloned() {
+            Some(recorded) => {
+                if recorded != checksum && self.desync_frame.is_none() {
+                    self.desync_frame = Some(self.current_frame);
+                    println!("Replay desynced on frame {}", self.current_frame);
+
+                    if let Some(recorded_players) = self.player_history.get(self.current_frame) {
+                        for (i, (recorded, simulated)) in recorded_players.iter().zip(self.players.iter()).enumerate() {
+                            for line in player_diff(recorded, simulated) {
+                                println!("    Player {}: {}", i, line);
+                            }
+                        }
+                    }
+                }
+            }
+            None => {
+                // the current frame of a paused game has not been recorded yet
+                if self.checksums.len() == self.current_frame {
+                    self.checksums.push(checksum);
+                }
+            }
+        }
+    }
+
     /// Immediately jumps to the previous frame in history
     fn step_replay_backwards(&mut self, input: &mut Input) {
         if self.current_frame > 0 {
             self.current_frame -= 1;
-            self.players = self.player_history.get(self.current_frame).unwrap().clone();
-            self.stage   = self.stage_history .get(self.current_frame).unwrap().clone();
+            self.players     = self.player_history    .get(self.current_frame).unwrap();
+            self.stage       = self.stage_history     .get(self.current_frame).unwrap().clone();
+            self.projectiles = self.projectile_history.get(self.current_frame).unwrap().clone();
             self.update_frame();
         }
         else {
@@ -923,28 +1151,91 @@ impl Game {
         }
     }
 
-    /// Jump to the saved frame in history
-    // TODO: FIX
-    //fn jump_frame(&mut self) {
-    //    let frame = self.saved_frame;
-    //    if (frame+1) < self.player_history.len() {
-    //        self.players = self.player_history.get(frame).unwrap().clone();
+    /// Immediately jumps to any frame.
+    /// Frames in history are restored directly, later frames are simulated from the input history.
+    fn jump_to_frame(&mut self, frame: usize, input: &mut Input, netplay: &Netplay) {
+        if frame < self.player_history.len() {
+            self.current_frame = frame;
+            self.players       = self.player_history    .get(frame).unwrap();
+            self.stage         = self.stage_history     .get(frame).unwrap().clone();
+            self.projectiles   = self.projectile_history.get(frame).unwrap().clone();
+        }
+        else {
+            // continue simulating from the end of the history
+            if self.current_frame + 1 < self.player_history.len() {
+                self.current_frame = self.player_history.len() - 1;
+                self.players       = self.player_history    .get(self.current_frame).unwrap();
+                self.stage         = self.stage_history     .get(self.current_frame).unwrap().clone();
+                self.projectiles   = self.projectile_history.get(self.current_frame).unwrap().clone();
+            }
+
+            while self.current_frame < frame && self.current_frame < input.history_len() {
+                self.step_replay_forwards(input, netplay);
+            }
+        }
+        self.update_frame();
+    }
+
+    fn timeline_visible(&self) -> bool {
+        match self.state {
+            GameState::Paused | GameState::ReplayForwards | GameState::ReplayBackwards => true,
+            _ => false
+        }
+    }
+
+    /// The mouse position in screen coordinates ranging from -1.0 to 1.0
+    fn screen_mouse(os_input: &WinitInputHelper<()>) -> Option<(f32, f32)> {
+        let (m_x, m_y) = os_input.mouse()?;
+        let (width, height) = os_input.resolution()?;
+        Some((m_x / width as f32 * 2.0 - 1.0, 1.0 - m_y / height as f32 * 2.0))
+    }
 
-    //        self.current_frame = frame;
-    //        self.update_frame();
-    //    }
-    //}
+    fn mouse_over_timeline(&self, os_input: &WinitInputHelper<()>) -> bool {
+        if let (true, Some((x, y))) = (self.timeline_visible(), Game::screen_mouse(os_input)) {
+            x >= TIMELINE_AREA.left() && x <= TIMELINE_AREA.right() && y >= TIMELINE_AREA.bot() && y <= TIMELINE_AREA.top()
+        } else {
+            false
+        }
+    }
+
+    /// The frame below the mouse, the mouse can leave the timeline while scrubbing
+    fn timeline_frame_at_mouse(&self, os_input: &WinitInputHelper<()>) -> Option<usize> {
+        let (x, _) = Game::screen_mouse(os_input)?;
+        let progress = ((x - TIMELINE_AREA.left()) / (TIMELINE_AREA.right() - TIMELINE_AREA.left())).max(0.0).min(1.0);
+        Some((progress * self.timeline.last_frame as f32).round() as usize)
+    }
 
     fn get_seed(&self) -> [u8; 32] {
+        self.seed_at(self.current_frame)
+    }
+
+    fn seed_at(&self, frame: usize) -> [u8; 32] {
         let mut seed = [0; 32];
         (&mut seed[0..8]).write_u64::<LittleEndian>(self.init_seed).unwrap();
-        (&mut seed[8..16]).write_u64::<LittleEndian>(self.current_frame as u64).unwrap();
+        (&mut seed[8..16]).write_u64::<LittleEndian>(frame as u64).unwrap();
         seed
     }
 
+    /// A deterministic hash of the simulation state recorded for frame, the same on every build so it can be compared against replays and netplay peers
+    pub fn checksum(&self, frame: usize) -> u64 {
+        let mut hasher = Fnv1a::default();
+        hasher.write(&self.seed_at(frame)); // position of the rng
+        hasher.write_u64(self.stage_checksum);
+        hasher.write_usize(self.players.len());
+        for player in &self.players {
+            player.hash_state(&mut hasher);
+        }
+        hasher.write_usize(self.projectiles.len());
+        for projectile in &self.projectiles {
+            projectile.hash_state(&mut hasher);
+        }
+        hasher.finish()
+    }
+
     fn step_game(&mut self, input: &Input, player_input: &Vec<PlayerInput>) {
         {
             let mut rng = ChaChaRng::from_seed(self.get_seed());
+            let surfaces = self.stage.surfaces_at(self.current_frame);
 
             // To synchronize player stepping, we step through player logic in stages (action logic, physics logic, collision logic)
             // Modified players are copied from the previous stage so that every player perceives themselves as being stepped first, within that stage.
@@ -959,7 +1250,8 @@ impl Game {
                     fighters: &self.package.fighters,
                     fighter:  &self.package.fighters[player.fighter.as_ref()],
                     stage:    &self.stage,
-                    surfaces: &self.stage.surfaces,
+                    surfaces: &surfaces,
+                    rules:    &self.package.rules,
                     rng:      &mut rng,
                     input,
                 };
@@ -977,17 +1269,35 @@ impl Game {
                     fighters: &self.package.fighters,
                     fighter:  &self.package.fighters[player.fighter.as_ref()],
                     stage:    &self.stage,
-                    surfaces: &self.stage.surfaces,
+                    surfaces: &surfaces,
+                    rules:    &self.package.rules,
                     rng:      &mut rng,
                     input,
                 };
-                player.physics_step(&mut context, i, self.current_frame, self.package.rules.goal.clone());
+                player.physics_step(&mut context, i, self.current_frame, self.goal());
                 physics_players.push(player);
             }
 
+            // step each projectile and spawn new projectiles
+            let mut physics_projectiles: Vec<Projectile> = vec!();
+            for projectile in &self.projectiles {
+                let mut projectile = projectile.clone();
+                if !projectile.step(&self.package.fighters, &self.stage) {
+                    physics_projectiles.push(projectile);
+                }
+            }
+            for (i, player) in physics_players.iter().enumerate() {
+                let bps = player.public_bps_xy(&physics_players, &self.package.fighters, &surfaces);
+                for spawn in &player.projectile_spawns {
+                    if let Some(projectile) = Projectile::new(i, player, bps, spawn, &self.package.fighters) {
+                        physics_projectiles.push(projectile);
+                    }
+                }
+            }
+
             // check for hits and run hit logic
             let mut collision_players: Vec<Player> = vec!();
-            let collision_results = collision_check(&physics_players, &self.package.fighters, &self.stage.surfaces);
+            let (collision_results, projectile_collision_results) = collision_check(&physics_players, &physics_projectiles, &self.package.fighters, &surfaces, &self.package.rules);
             for (i, player) in physics_players.iter().enumerate() {
                 let mut player = player.clone();
                 let input = &player_input[self.selected_controllers[i]];
@@ -996,94 +1306,299 @@ impl Game {
                     fighters: &self.package.fighters,
                     fighter:  &self.package.fighters[player.fighter.as_ref()],
                     stage:    &self.stage,
-                    surfaces: &self.stage.surfaces,
+                    surfaces: &surfaces,
+                    rules:    &self.package.rules,
                     rng:      &mut rng,
                     input,
                 };
                 player.step_collision(&mut context, &collision_results[i]);
+                player.step_hazards(&mut context, self.current_frame);
                 collision_players.push(player);
             }
 
+            let mut collision_projectiles: Vec<Projectile> = vec!();
+            for (i, projectile) in physics_projectiles.iter().enumerate() {
+                let mut projectile = projectile.clone();
+                if !projectile.step_collision(&physics_players, &self.package.fighters, &projectile_collision_results[i]) {
+                    collision_projectiles.push(projectile);
+                }
+            }
+
             self.players = collision_players;
+            self.projectiles = collision_projectiles;
+
+            self.step_team_stocks(player_input, &mut rng);
+
+            if let Some(round) = self.sudden_death_starting() {
+                self.start_sudden_death(round, player_input, &mut rng);
+            }
         }
 
-        if self.time_out() ||
-           (self.players.len() == 1 && self.players.iter().filter(|x| x.action != Action::Eliminated.to_u64().unwrap()).count() == 0) ||
-           (self.players.len() >  1 && self.players.iter().filter(|x| x.action != Action::Eliminated.to_u64().unwrap()).count() == 1)
-        {
+        let sudden_death_started = self.sudden_death.as_ref().map_or(false, |x| x.frame == self.current_frame);
+        if !sudden_death_started && (self.time_out() || self.last_side_standing()) {
             self.state = self.generate_game_results(input);
         }
 
         self.update_frame();
     }
 
+    /// Eliminated players take a stock from the teammate with the most stocks, when the rules allow it
+    fn step_team_stocks(&mut self, player_input: &[PlayerInput], rng: &mut ChaChaRng) {
+        let steal_only = match self.package.rules.teams {
+            Teams::On { stocks: TeamStocks::Shared, .. } => false,
+            Teams::On { stocks: TeamStocks::Steal, .. }  => true,
+            _                                            => return,
+        };
+        let surfaces = self.stage.surfaces_at(self.current_frame);
+
+        for player_i in 0..self.players.len() {
+            let input = &player_input[self.selected_controllers[player_i]];
+            let team = self.players[player_i].team;
+            if !self.players[player_i].is_eliminated() || (steal_only && !input.a.press) {
+                continue;
+            }
+
+            let mut donor: Option<usize> = None;
+            for (i, teammate) in self.players.iter().enumerate() {
+                let stocks = teammate.stocks.unwrap_or(0);
+                if teammate.team == team && !teammate.is_eliminated() && stocks > 1 && donor.map_or(true, |donor| stocks > self.players[donor].stocks.unwrap_or(0)) {
+                    donor = Some(i);
+                }
+            }
+
+            if let Some(donor_i) = donor {
+                self.players[donor_i].stocks = self.players[donor_i].stocks.map(|x| x - 1);
+
+                let mut player = self.players[player_i].clone();
+                {
+                    let mut context = StepContext {
+                        players:  &self.players,
+                        fighters: &self.package.fighters,
+                        fighter:  &self.package.fighters[player.fighter.as_ref()],
+                        stage:    &self.stage,
+                        surfaces: &surfaces,
+                        rules:    &self.package.rules,
+                        rng,
+                        input,
+                    };
+                    player.revive(&mut context, player_i);
+                }
+                self.players[player_i] = player;
+            }
+        }
+    }
+
+    /// The sudden death round starting on this frame.
+    /// A round already recorded for this frame is reused, so replays play back the same tiebreak even if the rules have changed.
+    fn sudden_death_starting(&self) -> Option<SuddenDeathRound> {
+        if let Some(ref round) = self.sudden_death {
+            return if round.frame == self.current_frame { Some(round.clone()) } else { None };
+        }
+
+        let rules = self.package.rules.sudden_death.as_ref()?;
+        if !self.time_out() {
+            return None;
+        }
+
+        let players = self.tied_for_first();
+        if players.len() == 0 {
+            return None;
+        }
+
+        // in stamina, the players must be left with some hp
+        let damage = match self.package.rules.goal.stamina_hp() {
+            Some(hp) => rules.damage.min(hp - 1.0).max(0.0),
+            None     => rules.damage,
+        };
+
+        Some(SuddenDeathRound {
+            frame:             self.current_frame,
+            time_limit_frames: rules.time_limit_frames(),
+            players,
+            damage,
+        })
+    }
+
+    /// Tied players respawn with a single stock and everyone else is eliminated
+    fn start_sudden_death(&mut self, round: SuddenDeathRound, player_input: &[PlayerInput], rng: &mut ChaChaRng) {
+        let surfaces = self.stage.surfaces_at(self.current_frame);
+        for player_i in 0..self.players.len() {
+            let input = &player_input[self.selected_controllers[player_i]];
+            let mut player = self.players[player_i].clone();
+            {
+                let mut context = StepContext {
+                    players:  &self.players,
+                    fighters: &self.package.fighters,
+                    fighter:  &self.package.fighters[player.fighter.as_ref()],
+                    stage:    &self.stage,
+                    surfaces: &surfaces,
+                    rules:    &self.package.rules,
+                    rng,
+                    input,
+                };
+                if round.players.contains(&player_i) {
+                    player.start_sudden_death(&mut context, player_i, round.damage);
+                }
+                else if !player.is_eliminated() {
+                    player.eliminate(&mut context);
+                }
+            }
+            self.players[player_i] = player;
+        }
+        self.sudden_death = Some(round);
+    }
+
+    /// Players still standing on the sides tied for first place, empty when there is no tie
+    fn tied_for_first(&self) -> Vec<usize> {
+        let raw_player_results: Vec<RawPlayerResult> = self.players.iter().map(|x| x.result()).collect();
+        let sides = self.sides();
+        let side_deaths = side_deaths(&sides, &raw_player_results);
+        let side_kills = side_kills(&sides, &credited_kills(&raw_player_results));
+        let scores = self.side_scores(&sides, &raw_player_results, &side_kills, &side_deaths);
+
+        let best = match scores.iter().max() {
+            Some(best) => *best,
+            None       => return vec!(),
+        };
+        let tied: Vec<&Vec<usize>> = sides.iter().zip(scores.iter()).filter(|(_, score)| **score == best).map(|(side, _)| side).collect();
+        if tied.len() < 2 {
+            return vec!();
+        }
+        tied.iter().flat_map(|side| side.iter().cloned()).filter(|i| !self.players[*i].is_eliminated()).collect()
+    }
+
+    /// KillDeathScore is played with stocks during sudden death
+    fn goal(&self) -> Goal {
+        match self.package.rules.goal {
+            Goal::KillDeathScore if self.sudden_death.as_ref().map_or(false, |x| self.current_frame > x.frame) => Goal::LastManStanding,
+            ref goal => goal.clone(),
+        }
+    }
+
+    /// Groups the indexes of players that are ranked together, each player is on their own when teams are off
+    fn sides(&self) -> Vec<Vec<usize>> {
+        match self.package.rules.teams {
+            Teams::On { .. } => {
+                let mut sides: Vec<Vec<usize>> = vec!();
+                for (i, player) in self.players.iter().enumerate() {
+                    match sides.iter().position(|side| self.players[side[0]].team == player.team) {
+                        Some(side_i) => sides[side_i].push(i),
+                        None         => sides.push(vec!(i)),
+                    }
+                }
+                sides
+            }
+            Teams::Off => (0..self.players.len()).map(|i| vec!(i)).collect()
+        }
+    }
+
+    /// The game is over when only one side has players remaining, or when a lone side is eliminated
+    fn last_side_standing(&self) -> bool {
+        let sides = self.sides();
+        let sides_remaining = sides.iter().filter(|side| side.iter().any(|i| !self.players[*i].is_eliminated())).count();
+        match sides.len() {
+            0 => false,
+            1 => sides_remaining == 0,
+            _ => sides_remaining <= 1,
+        }
+    }
+
+    /// The frame the game times out after, a sudden death round has its own time limit
+    fn time_limit_frame(&self) -> Option<u64> {
+        match self.sudden_death {
+            Some(ref round) if self.current_frame >= round.frame => round.time_limit_frames.map(|x| x + round.frame as u64),
+            _                                                    => self.package.rules.time_limit_frames()
+        }
+    }
+
     pub fn time_out(&self) -> bool {
-        if let Some(time_limit_frames) = self.package.rules.time_limit_frames() {
-            self.current_frame as u64 > time_limit_frames
+        if let Some(time_limit_frame) = self.time_limit_frame() {
+            self.current_frame as u64 > time_limit_frame
         } else {
             false
         }
     }
 
+    /// A higher score places higher, sides with the same score are separated by tie breakers or sudden death
+    fn side_scores(&self, sides: &[Vec<usize>], raw_player_results: &[RawPlayerResult], side_kills: &[usize], side_deaths: &[usize]) -> Vec<i64> {
+        let rules = &self.package.rules;
+        sides.iter().enumerate().map(|(side_i, side)| {
+            match rules.goal {
+                // sides with a player still standing beat eliminated sides, then most stocks remaining wins
+                // stocks are used instead of deaths as handicaps can give players extra stocks
+                Goal::LastManStanding | Goal::Stamina { .. } => {
+                    if side.iter().all(|i| self.players[*i].is_eliminated()) {
+                        i64::MIN
+                    } else {
+                        let stocks: Option<u64> = side.iter().map(|i| self.players[*i].stocks).sum();
+                        match stocks {
+                            Some(stocks) => stocks as i64,
+                            None         => -(side_deaths[side_i] as i64) // infinite stocks
+                        }
+                    }
+                }
+                Goal::KillDeathScore => {
+                    let self_destructs: usize = side.iter().map(|i| raw_player_results[*i].deaths.iter().filter(|x| x.player.is_none()).count()).sum();
+                    side_kills[side_i] as i64 - side_deaths[side_i] as i64 - self_destructs as i64 * rules.self_destruct_penalty as i64
+                }
+            }
+        }).collect()
+    }
+
     pub fn generate_game_results(&self, input: &Input) -> GameState {
         let raw_player_results: Vec<RawPlayerResult> = self.players.iter().map(|x| x.result()).collect();
-        // TODO: Players on the same team score to the same pool, and share their place.
-        let places: Vec<usize> = match self.package.rules.goal {
-            Goal::LastManStanding => {
-                // most stocks remaining wins
+        let kills = credited_kills(&raw_player_results);
+
+        // teammates are ranked together, so they share a place and their stats are combined
+        let sides = self.sides();
+        let side_deaths = side_deaths(&sides, &raw_player_results);
+        let side_kills = side_kills(&sides, &kills);
+        let scores = self.side_scores(&sides, &raw_player_results, &side_kills, &side_deaths);
+
+        let mut side_order: Vec<usize> = (0..sides.len()).collect();
+        match self.package.rules.goal {
+            Goal::LastManStanding | Goal::Stamina { .. } => {
+                // sides with a player still standing win
                 // tie-breaker:
+                //  * most stocks remaining wins
+                //  * least deaths wins
                 //  * if both eliminated: who lost their last stock last wins
                 //  * if both alive:      lowest percentage wins
-                let mut raw_player_results_i: Vec<(usize, &RawPlayerResult)> = raw_player_results.iter().enumerate().collect();
-                raw_player_results_i.sort_by(
-                    |a_set, b_set| {
-                        let a = a_set.1;
-                        let b = b_set.1;
-                        let a_deaths = a.deaths.len();
-                        let b_deaths = b.deaths.len();
-                        a_deaths.cmp(&b_deaths).then(
-                            if a_deaths == 0 {
-                                if let Some(death_a) = a.deaths.last() {
-                                    if let Some(death_b) = b.deaths.last() {
-                                        death_a.frame.cmp(&death_b.frame)
-                                    }
-                                    else {
-                                        Ordering::Equal
-                                    }
-                                }
-                                else {
-                                    Ordering::Equal
-                                }
+                let eliminated: Vec<bool> = sides.iter().map(|side| side.iter().all(|i| self.players[*i].is_eliminated())).collect();
+                let last_death: Vec<usize> = sides.iter().map(|side| side.iter().filter_map(|i| raw_player_results[*i].deaths.last().map(|x| x.frame)).max().unwrap_or(0)).collect();
+                let damage: Vec<f32> = sides.iter().map(|side| side.iter().map(|i| raw_player_results[*i].final_damage.unwrap_or(0.0)).sum()).collect();
+                side_order.sort_by(
+                    |a, b| {
+                        scores[*b].cmp(&scores[*a]).then(side_deaths[*a].cmp(&side_deaths[*b])).then(
+                            if eliminated[*a] {
+                                last_death[*b].cmp(&last_death[*a])
                             }
                             else {
-                                a.final_damage.unwrap().partial_cmp(&b.final_damage.unwrap()).unwrap_or(Ordering::Equal)
+                                damage[*a].partial_cmp(&damage[*b]).unwrap_or(Ordering::Equal)
                             }
                         )
                     }
                 );
-                raw_player_results_i.iter().map(|x| x.0).collect()
             }
             Goal::KillDeathScore => {
-                // highest kills wins
+                // highest score wins
                 // tie breaker: least deaths wins
-                let mut raw_player_results_i: Vec<(usize, &RawPlayerResult)> = raw_player_results.iter().enumerate().collect();
-                raw_player_results_i.sort_by(
-                    |a_set, b_set| {
-                        // Repopulating kill lists every frame shouldnt be too bad
-                        let a_kills: Vec<usize> = vec!(); // TODO: populate
-                        let b_kills: Vec<usize> = vec!(); // TODO: populate
-                        let a = a_set.1;
-                        let b = b_set.1;
-                        let a_kills = a_kills.len();
-                        let b_kills = b_kills.len();
-                        let a_deaths = a.deaths.len();
-                        let b_deaths = b.deaths.len();
-                        b_kills.cmp(&a_kills).then(a_deaths.cmp(&b_deaths))
-                    }
-                );
-                raw_player_results_i.iter().map(|x| x.0).collect()
+                side_order.sort_by(|a, b| scores[*b].cmp(&scores[*a]).then(side_deaths[*a].cmp(&side_deaths[*b])));
             }
-        };
+        }
+
+        let mut places = vec!(0; raw_player_results.len());
+        let mut team_kills = vec!(None; raw_player_results.len());
+        let mut team_deaths = vec!(None; raw_player_results.len());
+        for (place, side_i) in side_order.iter().enumerate() {
+            for player_i in &sides[*side_i] {
+                places[*player_i] = place;
+                if let Teams::On { .. } = self.package.rules.teams {
+                    team_kills[*player_i] = Some(side_kills[*side_i]);
+                    team_deaths[*player_i] = Some(side_deaths[*side_i]);
+                }
+            }
+        }
 
         let mut player_results: Vec<PlayerResult> = vec!();
         for (i, raw_player_result) in raw_player_results.iter().enumerate() {
@@ -1098,8 +1613,10 @@ impl Game {
                 team:            raw_player_result.team,
                 controller:      self.selected_controllers[i],
                 place:           places[i],
-                kills:           vec!(), // TODO
+                kills:           kills[i].clone(),
                 deaths:          raw_player_result.deaths.clone(),
+                team_kills:      team_kills[i],
+                team_deaths:     team_deaths[i],
                 lcancel_percent: lcancel_percent,
             });
         }
@@ -1126,7 +1643,18 @@ impl Game {
             let fighter = &self.package.fighters[self.players[i].fighter.as_ref()];
             let player_input = &player_inputs[self.selected_controllers[i]];
             let debug_player = &self.debug_players[i];
-            self.debug_lines.extend(player.debug_print(fighter, player_input, debug_player, i));
+            self.debug_lines.extend(player.debug_print(fighter, &self.package.rules, player_input, debug_player, i));
+        }
+
+        if let (&GameState::Paused, &Edit::Stage) = (&self.state, &self.edit) {
+            for i in self.selector.surfaces_vec() {
+                if let Some(ref motion) = self.stage.surfaces[i].motion {
+                    self.debug_lines.push(format!(
+                        "Surface: {}    path points: {}    rotation speed: {:.3}    visible frames: {}    hidden frames: {}",
+                        i, motion.path.len(), motion.rotation_speed, motion.visible_frames, motion.hidden_frames
+                    ));
+                }
+            }
         }
 
         if self.debug_output_this_step {
@@ -1145,9 +1673,16 @@ impl Game {
         self.debug_output_this_step = true;
     }
 
+    /// surfaces as they are positioned on the current frame
+    fn surfaces(&self) -> Cow<[Surface]> {
+        self.stage.surfaces_at(self.current_frame)
+    }
+
     #[allow(unused)] // Needed for headless build
     pub fn render(&self) -> RenderGame {
         let mut entities = vec!();
+        let surfaces = self.surfaces();
+
 
         for (i, player) in self.players.iter().enumerate() {
             let mut selected_colboxes = HashSet::new();
@@ -1170,16 +1705,22 @@ impl Game {
 
             let debug = self.debug_players[i].clone();
             if debug.cam_area {
-                let cam_area = player.cam_area(&self.stage.camera, &self.players, &self.package.fighters, &self.stage.surfaces);
+                let cam_area = player.cam_area(&self.stage.camera, &self.players, &self.package.fighters, &surfaces);
                 entities.push(RenderEntity::rect_outline(cam_area, 0.0, 0.0, 1.0));
             }
 
             let fighters = &self.package.fighters;
-            let surfaces = &self.stage.surfaces;
-            let player_render = player.render(selected_colboxes, fighter_selected, player_selected, debug, i, &self.player_history[0..self.current_frame], &self.players, fighters, surfaces);
+            let mut player_render = player.render(selected_colboxes, fighter_selected, player_selected, debug, i, self.recent_players.frames(), &self.players, fighters, &surfaces);
+            player_render.hp = self.package.rules.goal.stamina_hp().map(|hp| (hp - player.damage).max(0.0));
             entities.push(RenderEntity::Player(player_render));
         }
 
+        for projectile in &self.projectiles {
+            if let Some(render_projectile) = projectile.render(&self.package.fighters) {
+                entities.push(RenderEntity::Projectile(render_projectile));
+            }
+        }
+
         // render stage debug entities
         if self.debug_stage.blast {
             entities.push(RenderEntity::rect_outline(self.stage.blast.clone(),  1.0, 0.0, 0.0));
@@ -1206,6 +1747,36 @@ impl Game {
             }
         }
 
+        if self.debug_stage.hazards {
+            for hazard in self.stage.hazards.iter() {
+                if let Some(bounds) = hazard.bounds(&surfaces) {
+                    if hazard.active(self.current_frame) {
+                        entities.push(RenderEntity::rect_outline(bounds, 1.0, 0.5, 0.0));
+                    } else {
+                        entities.push(RenderEntity::rect_outline(bounds, 0.4, 0.2, 0.0));
+                    }
+                }
+            }
+        }
+        if self.debug_stage.surface_paths {
+            for (i, surface) in self.stage.surfaces.iter().enumerate() {
+                if let Some(ref motion) = surface.motion {
+                    let selected = self.selector.surfaces.contains(&SurfaceSelection::P1(i)) || self.selector.surfaces.contains(&SurfaceSelection::P2(i));
+                    let (c_x, c_y) = surface.center();
+                    for point in motion.path.iter() {
+                        let x = c_x + point.x;
+                        let y = c_y + point.y;
+                        let rect = Rect { x1: x - 1.0, y1: y - 1.0, x2: x + 1.0, y2: y + 1.0 };
+                        if selected {
+                            entities.push(RenderEntity::rect_outline(rect, 0.0, 1.0, 0.0));
+                        } else {
+                            entities.push(RenderEntity::rect_outline(rect, 0.0, 1.0, 1.0));
+                        }
+                    }
+                }
+            }
+        }
+
         // render selector box
         if let Some(point) = self.selector.point {
             if let Some(mouse) = self.selector.mouse {
@@ -1214,23 +1785,41 @@ impl Game {
             }
         }
 
-        let timer = if let Some(time_limit_frames) = self.package.rules.time_limit_frames() {
-            let frames_remaining = time_limit_frames.saturating_sub(self.current_frame as u64);
+        let timer = if let Some(time_limit_frame) = self.time_limit_frame() {
+            let frames_remaining = time_limit_frame.saturating_sub(self.current_frame as u64);
             let frame_duration = Duration::new(1, 0) / 60;
             Some(frame_duration * frames_remaining as u32)
         } else {
             None
         };
 
+        let timeline = if self.timeline_visible() {
+            Some(RenderTimeline {
+                last_frame:    self.timeline.last_frame,
+                current_frame: self.current_frame,
+                saved_frame:   self.saved_frame,
+                events:        self.timeline.events.clone(),
+            })
+        } else {
+            None
+        };
+
+        // the stage editor works on the rest position of the surfaces, otherwise hidden surfaces are not drawn
+        let surfaces = match (&self.state, &self.edit) {
+            (&GameState::Paused, &Edit::Stage) => self.stage.surfaces.to_vec(),
+            _                                  => self.stage.visible_surfaces_at(self.current_frame)
+        };
+
         RenderGame {
             seed:              self.get_seed(),
-            surfaces:          self.stage.surfaces.to_vec(),
+            surfaces:          surfaces,
             selected_surfaces: self.selector.surfaces.clone(),
             entities:          entities,
             state:             self.state.clone(),
             camera:            self.camera.clone(),
             debug_lines:       self.debug_lines.clone(),
             timer:             timer,
+            timeline:          timeline,
         }
     }
 
@@ -1249,10 +1838,101 @@ impl Game {
     }
 
     pub fn reclaim(self) -> (Package, Config) {
-        (self.package, self.config)
+        (self.replaced_package.unwrap_or(self.package), self.config)
+    }
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME:        u64 = 0x0000_0100_0000_01b3;
+
+/// 64 bit FNV-1a, std's hashers are not guaranteed to give the same result between builds.
+/// Integers are written little endian and usize as u64 so checksums match across platforms.
+struct Fnv1a (u64);
+
+impl Default for Fnv1a {
+    fn default() -> Fnv1a {
+        Fnv1a (FNV_OFFSET_BASIS)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn write_u16(&mut self, value: u16) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+
+    fn write_i64(&mut self, value: i64) {
+        self.write_u64(value as u64);
+    }
+
+    fn write_isize(&mut self, value: isize) {
+        self.write_u64(value as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
     }
 }
 
+/// Lists each recorded field that differs between the recorded and re-simulated player
+pub fn player_diff(recorded: &Player, simulated: &Player) -> Vec<String> {
+    let mut diff = vec!();
+    if let (Ok(Value::Object(recorded)), Ok(Value::Object(simulated))) = (serde_json::to_value(recorded), serde_json::to_value(simulated.recorded())) {
+        for (field, recorded_value) in &recorded {
+            let simulated_value = simulated.get(field).unwrap_or(&Value::Null);
+            if recorded_value != simulated_value {
+                diff.push(format!("{}: recorded {} simulated {}", field, recorded_value, simulated_value));
+            }
+        }
+    }
+    diff
+}
+
+/// Every death credited to a player is a kill for that player
+fn credited_kills(raw_player_results: &[RawPlayerResult]) -> Vec<Vec<DeathRecord>> {
+    (0..raw_player_results.len()).map(|killer_i| {
+        let mut kills = vec!();
+        for (victim_i, raw_player_result) in raw_player_results.iter().enumerate() {
+            for death in &raw_player_result.deaths {
+                if death.player == Some(killer_i) {
+                    kills.push(DeathRecord { player: Some(victim_i), frame: death.frame });
+                }
+            }
+        }
+        kills.sort_by_key(|x| x.frame);
+        kills
+    }).collect()
+}
+
+fn side_deaths(sides: &[Vec<usize>], raw_player_results: &[RawPlayerResult]) -> Vec<usize> {
+    sides.iter().map(|side| side.iter().map(|i| raw_player_results[*i].deaths.len()).sum()).collect()
+}
+
+fn side_kills(sides: &[Vec<usize>], kills: &[Vec<DeathRecord>]) -> Vec<usize> {
+    sides.iter().map(|side| {
+        // killing a teammate does not count
+        side.iter().map(|i| kills[*i].iter().filter(|x| x.player.map_or(true, |victim| !side.contains(&victim))).count()).sum()
+    }).collect()
+}
+
 #[derive(Clone, Serialize, Deserialize, Node)]
 pub enum GameState {
     Local,
@@ -1417,10 +2097,19 @@ pub struct RenderGame {
     pub camera:            Camera,
     pub debug_lines:       Vec<String>,
     pub timer:             Option<Duration>,
+    pub timeline:          Option<RenderTimeline>,
+}
+
+pub struct RenderTimeline {
+    pub last_frame:    usize,
+    pub current_frame: usize,
+    pub saved_frame:   usize,
+    pub events:        Vec<TimelineEvent>,
 }
 
 pub enum RenderEntity {
     Player      (RenderPlayer),
+    Projectile  (RenderProjectile),
     RectOutline (RenderRect),
     SpawnPoint  (RenderSpawnPoint),
 }
@@ -1461,15 +2150,19 @@ pub struct RenderSpawnPoint {
 
 #[derive(Clone)]
 pub struct GameSetup {
-    pub init_seed:      u64,
-    pub input_history:  Vec<Vec<ControllerInput>>,
-    pub player_history: Vec<Vec<Player>>,
-    pub stage_history:  Vec<Stage>,
-    pub controllers:    Vec<usize>,
-    pub players:        Vec<PlayerSetup>,
-    pub ais:            Vec<usize>,
-    pub stage:          String,
-    pub state:          GameState,
+    pub init_seed:          u64,
+    pub input_history:      Vec<Vec<ControllerInput>>,
+    pub player_history:     PlayerHistory,
+    pub stage_history:      Vec<Stage>,
+    pub projectile_history: Vec<Vec<Projectile>>,
+    pub controllers:        Vec<usize>,
+    pub players:            Vec<PlayerSetup>,
+    pub ais:                Vec<usize>,
+    pub stage:              String,
+    pub state:              GameState,
+    pub sudden_death:       Option<SuddenDeathRound>,
+    pub checksums:          Vec<u64>,
+    pub snapshot:           Option<PackageSnapshot>, // replaces the package data used by the game
 }
 
 impl GameSetup {
@@ -1478,8 +2171,38 @@ impl GameSetup {
     }
 }
 
+/// A sudden death round that started at the end of frame, kept in replays so they play back the same tiebreak
+#[derive(Clone, Default, Serialize, Deserialize, Node)]
+pub struct SuddenDeathRound {
+    pub frame:             usize,
+    pub players:           Vec<usize>, // players taking part, everyone else was eliminated
+    pub damage:            f32,
+    pub time_limit_frames: Option<u64>,
+}
+
 #[derive(Clone, Default, Serialize, Deserialize, Node)]
 pub struct PlayerSetup {
-    pub fighter: String,
-    pub team:    usize,
+    pub fighter:  String,
+    pub team:     usize,
+    pub handicap: Handicap,
+}
+
+/// Adjusts a single player to even out matches between players of different skill
+#[derive(Clone, PartialEq, Serialize, Deserialize, Node)]
+pub struct Handicap {
+    pub damage:               f32, // damage the player starts each stock with
+    pub damage_taken_mult:    f32,
+    pub knockback_dealt_mult: f32,
+    pub extra_stocks:         u64, // added to the stock count of the rules, no effect without stocks
+}
+
+impl Default for Handicap {
+    fn default() -> Self {
+        Handicap {
+            damage:               0.0,
+            damage_taken_mult:    1.0,
+            knockback_dealt_mult: 1.0,
+            extra_stocks:         0,
+        }
+    }
 }
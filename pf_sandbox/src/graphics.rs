@@ -1,6 +1,6 @@
 use pf_sandbox_lib::fighter::CollisionBoxRole;
 use pf_sandbox_lib::package::PackageUpdate;
-use crate::game::RenderGame;
+use crate::game::{RenderGame, MAX_PLAYERS};
 use crate::menu::RenderMenu;
 
 pub struct GraphicsMessage {
@@ -47,13 +47,21 @@ pub fn get_team_color3(i: usize) -> [f32; 3] {
     [color[0]/255.0, color[1]/255.0, color[2]/255.0]
 }
 
+#[allow(unused)] // Needed for headless build
+pub fn get_team_name(i: usize) -> String {
+    let colors = get_colors();
+    colors[i % colors.len()].name.clone()
+}
+
 pub struct Color {
     pub name: String,
     pub value: [f32; 3]
 }
 
+/// Returns a color for every team that can exist in a match.
+/// The named colors are used first, any extra teams get a generated color.
 pub fn get_colors() -> Vec<Color> {
-    vec!(
+    let mut colors = vec!(
         Color { name: String::from("Blue"),   value: [0.0,   90.0,   224.0] },
         Color { name: String::from("Orange"), value: [239.0, 100.0,  0.0] },
         Color { name: String::from("Red"),    value: [255.0, 0.0,    40.0] },
@@ -63,5 +71,32 @@ pub fn get_colors() -> Vec<Color> {
         Color { name: String::from("Green #2"),   value: [124.0, 184.0, 0.0] },
         Color { name: String::from("Purple"),     value: [120.0, 46.0,  252.0] },
         Color { name: String::from("Light Blue"), value: [81.0,  229.0, 237.0] },
-    )
+    );
+
+    // step the hue by the golden ratio so each generated color is far from the previous ones
+    let mut hue = 0.1;
+    while colors.len() < MAX_PLAYERS {
+        hue = (hue + 0.618_034) % 1.0;
+        let name = format!("Team {}", colors.len() + 1);
+        colors.push(Color { name, value: hsv_to_rgb(hue, 0.8, 230.0) });
+    }
+
+    colors
+}
+
+/// hue and saturation are from 0.0 to 1.0, the returned color uses the same range as value
+fn hsv_to_rgb(hue: f32, saturation: f32, value: f32) -> [f32; 3] {
+    let h = hue * 6.0;
+    let c = value * saturation;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let m = value - c;
+    let (r, g, b) = match h as usize {
+        0 => (c,   x,   0.0),
+        1 => (x,   c,   0.0),
+        2 => (0.0, c,   x),
+        3 => (0.0, x,   c),
+        4 => (x,   0.0, c),
+        _ => (c,   0.0, x),
+    };
+    [r + m, g + m, b + m]
 }
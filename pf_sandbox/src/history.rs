@@ -0,0 +1,197 @@
+use crate::game::Handicap;
+use crate::player::{Player, Location, LockTimer, Hitlag};
+use crate::results::RawPlayerResult;
+
+use pf_sandbox_lib::fighter::{ECB, ProjectileSpawn};
+use pf_sandbox_lib::history::{Delta, History};
+
+use treeflection::{Node, NodeRunner, NodeToken};
+
+/// The players on every frame of a game, particles and the debug display fields are not recorded
+pub type PlayerHistory = History<Player>;
+
+/// Declares PlayerDelta with a field for each recorded player field, None when the field did not change since the previous frame.
+/// Every other player field is listed with the value it is reset to, so a new player field fails to compile until it is listed in one or the other.
+macro_rules! player_delta {
+    (recorded { $($field:ident: $ty:ty,)* } reset { $($reset_field:ident: $reset:expr,)* }) => {
+        /// The fields of a player that changed since the previous frame
+        #[derive(Clone, Default, Serialize, Deserialize)]
+        pub struct PlayerDelta {
+            $(
+                #[serde(default, skip_serializing_if = "Option::is_none")]
+                $field: Option<$ty>,
+            )*
+        }
+
+        impl Delta for Player {
+            type Delta = PlayerDelta;
+
+            fn recorded(&self) -> Player {
+                Player {
+                    $($field: self.$field.clone(),)*
+                    $($reset_field: $reset,)*
+                }
+            }
+
+            fn delta(&self, previous: &Player) -> PlayerDelta {
+                PlayerDelta {
+                    $($field: if self.$field != previous.$field { Some(self.$field.clone()) } else { None },)*
+                }
+            }
+
+            fn apply(&mut self, delta: &PlayerDelta) {
+                $(
+                    if let Some(value) = &delta.$field {
+                        self.$field = value.clone();
+                    }
+                )*
+            }
+        }
+    }
+}
+
+player_delta! {
+    recorded {
+        fighter:            String,
+        team:               usize,
+        handicap:           Handicap,
+        action:             u64,
+        set_action_called:  bool,
+        new_action:         bool,
+        frame:              i64,
+        frame_norestart:    i64,
+        stocks:             Option<u64>,
+        damage:             f32,
+        location:           Location,
+        x_vel:              f32,
+        y_vel:              f32,
+        kb_x_vel:           f32,
+        kb_y_vel:           f32,
+        kb_x_dec:           f32,
+        kb_y_dec:           f32,
+        face_right:         bool,
+        frames_since_ledge: u64,
+        ledge_idle_timer:   u64,
+        fastfalled:         bool,
+        air_jumps_left:     u64,
+        jumpsquat_button:   bool,
+        shield_hp:          f32,
+        shield_analog:      f32,
+        shield_offset_x:    f32,
+        shield_offset_y:    f32,
+        stun_timer:         u64,
+        shield_stun_timer:  u64,
+        parry_timer:        u64,
+        tech_timer:         LockTimer,
+        lcancel_timer:      u64,
+        land_frame_skip:    u8,
+        ecb:                ECB,
+        hitlist:            Vec<usize>,
+        hitlag:             Hitlag,
+        hitstun:            f32,
+        hit_by:             Option<usize>,
+        hit_by_timer:       u64,
+        grabbing:           Option<usize>,
+        hazard_timer:       u64,
+        stale_moves:        Vec<u64>,
+        intangible_timer:   u64,
+        ledge_grabs:        u64,
+        grab_timer:         u64,
+        projectile_spawns:  Vec<ProjectileSpawn>,
+        aerial_dodge_frame: Option<u64>,
+        result:             RawPlayerResult,
+    }
+    reset {
+        particles:          vec!(),
+        frames_since_hit:   0,
+        hit_angle_pre_di:   None,
+        hit_angle_post_di:  None,
+        sdi_offset:         None,
+        asdi_offset:        None,
+        stick:              None,
+        c_stick:            None,
+    }
+}
+
+/// Hits and deaths found in the player history, displayed on the replay timeline.
+/// Only updated while the timeline is visible, so new frames are searched all at once when it is shown.
+#[derive(Clone, Default, Serialize, Deserialize, Node)]
+pub struct Timeline {
+    pub events:     Vec<TimelineEvent>,
+    pub last_frame: usize, // the last frame that can be jumped to
+    searched:       usize, // frames of history already searched for events
+    previous:       Vec<PlayerEventState>, // the players on the last frame searched, empty when it needs to be decoded again
+}
+
+impl Timeline {
+    /// Search any newly recorded frames for events
+    pub fn update(&mut self, history: &PlayerHistory, last_frame: usize) {
+        self.last_frame = last_frame;
+        if history.len() < self.searched {
+            self.truncate(history.len());
+        }
+        if history.len() > self.searched {
+            // without the players of the last frame searched, start a frame early so changes on the first new frame can be found
+            let start = if self.previous.is_empty() { self.searched.saturating_sub(1) } else { self.searched };
+            let events = &mut self.events;
+            let previous_players = &mut self.previous;
+            history.for_each(start..history.len(), |frame, players| {
+                let current_players: Vec<PlayerEventState> = players.iter().map(PlayerEventState::new).collect();
+                for (player, (previous, current)) in previous_players.iter().zip(current_players.iter()).enumerate() {
+                    if current.launched && !previous.launched {
+                        events.push(TimelineEvent { frame, player, kind: TimelineEventKind::Hit });
+                    }
+                    if current.deaths > previous.deaths {
+                        events.push(TimelineEvent { frame, player, kind: TimelineEventKind::Death });
+                    }
+                }
+                *previous_players = current_players;
+            });
+            self.searched = history.len();
+        }
+    }
+
+    /// Forget events from frame len onwards, they need to be searched again when the history is rewritten
+    pub fn truncate(&mut self, len: usize) {
+        self.events.retain(|event| event.frame < len);
+        if len < self.searched {
+            self.searched = len;
+            self.previous.clear();
+        }
+    }
+}
+
+/// The parts of a player that timeline events are found from
+#[derive(Clone, Default, Serialize, Deserialize, Node)]
+struct PlayerEventState {
+    launched: bool, // in hitlag from a hit that launches the player
+    deaths:   usize,
+}
+
+impl PlayerEventState {
+    fn new(player: &Player) -> PlayerEventState {
+        PlayerEventState {
+            launched: if let Hitlag::Launch { .. } = player.hitlag { true } else { false },
+            deaths:   player.result.deaths.len(),
+        }
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, Node)]
+pub struct TimelineEvent {
+    pub frame:  usize,
+    pub player: usize,
+    pub kind:   TimelineEventKind,
+}
+
+#[derive(Clone, Serialize, Deserialize, Node)]
+pub enum TimelineEventKind {
+    Hit,
+    Death,
+}
+
+impl Default for TimelineEventKind {
+    fn default() -> TimelineEventKind {
+        TimelineEventKind::Hit
+    }
+}
@@ -20,8 +20,11 @@ use libusb::{Context, Device, DeviceHandle, Error};
 
 use pf_sandbox_lib::network::{Netplay, NetplayState};
 
+/// Number of controller ports on a single GC adapter, use multiple adapters for more players
+const GC_ADAPTER_PORTS: usize = 4;
+
 enum InputSource<'a> {
-    GCAdapter { handle: DeviceHandle<'a>, deadzones: [Deadzone; 4] },
+    GCAdapter { handle: DeviceHandle<'a>, deadzones: Vec<Deadzone> },
     GenericController { index: usize, state: ControllerInput, deadzone: Deadzone }
 }
 
@@ -75,7 +78,7 @@ impl<'a> Input<'a> {
 
         let mut input_sources = vec!();
         for handle in adapter_handles {
-            input_sources.push(InputSource::GCAdapter { handle, deadzones: Deadzone::empty4() });
+            input_sources.push(InputSource::GCAdapter { handle, deadzones: Deadzone::empty_vec(GC_ADAPTER_PORTS) });
         }
 
         let gilrs = Gilrs::new().unwrap();
@@ -128,7 +131,7 @@ impl<'a> Input<'a> {
         if reset_deadzones {
             for source in &mut self.input_sources {
                 match source {
-                    &mut InputSource::GCAdapter         { ref mut deadzones, .. } => { *deadzones = Deadzone::empty4() }
+                    &mut InputSource::GCAdapter         { ref mut deadzones, .. } => { *deadzones = Deadzone::empty_vec(GC_ADAPTER_PORTS) }
                     &mut InputSource::GenericController { ref mut deadzone,  .. } => { *deadzone  = Deadzone::empty() }
                 }
             }
@@ -244,7 +247,9 @@ impl<'a> Input<'a> {
             if i == local_index {
                 peer_offset = 1;
 
-                for i in 0..self.current_inputs.len() {
+                // use the controllers recorded on that frame, so replays can be played back with any controllers plugged in
+                let num_controllers = self.game_inputs.get(frame).map_or(self.current_inputs.len(), |x| x.len());
+                for i in 0..num_controllers {
                     let inputs = self.get_8frames_of_input(&self.game_inputs, i, frame as i64);
                     result_inputs.push(Input::controller_inputs_to_player_input(inputs));
                 }
@@ -340,6 +345,11 @@ impl<'a> Input<'a> {
         self.game_inputs.len() - 1
     }
 
+    /// Number of frames in the game input history
+    pub fn history_len(&self) -> usize {
+        self.game_inputs.len()
+    }
+
     /// The player input history system cannot be used when the game is paused (or it would create bogus entries into the history)
     /// Instead we need to create custom functions for handling input when paused.
 
@@ -366,11 +376,11 @@ fn display_endpoints(device: &mut Device) {
     }
 }
 
-/// Add 4 GC adapter controllers to inputs
+/// Add a controller for each GC adapter port to inputs
 fn read_gc_adapter(handle: &mut DeviceHandle, deadzones: &mut [Deadzone], inputs: &mut Vec<ControllerInput>) {
-    let mut data: [u8; 37] = [0; 37];
+    let mut data = [0u8; 1 + 9 * GC_ADAPTER_PORTS];
     if let Ok(_) = handle.read_interrupt(0x81, &mut data, Duration::new(1, 0)) {
-        for port in 0..4 {
+        for port in 0..GC_ADAPTER_PORTS {
             let plugged_in    = data[9*port+1] == 20 || data[9*port+1] == 16;
             let raw_stick_x   = data[9*port+4];
             let raw_stick_y   = data[9*port+5];
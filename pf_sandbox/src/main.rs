@@ -11,12 +11,15 @@ pub(crate) mod cli;
 pub(crate) mod collision;
 pub(crate) mod game;
 pub(crate) mod graphics;
+pub(crate) mod history;
 pub(crate) mod input;
 pub(crate) mod menu;
 pub(crate) mod particle;
 pub(crate) mod player;
+pub(crate) mod projectile;
 pub(crate) mod replays;
 pub(crate) mod results;
+pub(crate) mod set;
 
 #[cfg(feature = "wgpu_renderer")]
 pub(crate) mod wgpu;
@@ -4,12 +4,14 @@ use pf_sandbox_lib::input::PlayerInput;
 use pf_sandbox_lib::network::{Netplay, NetplayState};
 use pf_sandbox_lib::package::{Package, PackageMeta, Verify};
 use pf_sandbox_lib::package;
-use crate::game::{GameSetup, GameState, PlayerSetup};
+use crate::game::{GameSetup, GameState, PlayerSetup, Handicap, MAX_PLAYERS};
 use crate::graphics::{GraphicsMessage, Render, RenderType};
 use crate::graphics;
+use crate::history::PlayerHistory;
 use crate::input::Input;
 use crate::replays;
 use crate::results::{GameResults, PlayerResult};
+use crate::set::Set;
 
 use treeflection::{Node, NodeRunner, NodeToken};
 use winit::event::VirtualKeyCode;
@@ -19,6 +21,17 @@ use std::sync::mpsc::{Sender, Receiver, channel, TryRecvError};
 use std::thread;
 use std::mem;
 
+// values a handicap setting cycles through on the character select screen
+const HANDICAP_DAMAGE:       [f32; 7] = [0.0, 10.0, 20.0, 30.0, 50.0, 75.0, 100.0];
+const HANDICAP_MULTS:        [f32; 7] = [1.0, 1.1, 1.25, 1.5, 0.5, 0.75, 0.9];
+const HANDICAP_EXTRA_STOCKS: [u64; 4] = [0, 1, 2, 3];
+
+/// The value after current in values, wrapping around to the first value
+fn next_handicap_value<T: PartialEq + Copy>(values: &[T], current: T) -> T {
+    let i = values.iter().position(|x| *x == current).map_or(0, |i| (i + 1) % values.len());
+    values[i]
+}
+
 /// For player convenience some data is kept when moving between menus.
 /// This data is stored in the Menu struct.
 ///
@@ -40,6 +53,7 @@ pub struct Menu {
     game_setup:         Option<GameSetup>,
     package_loader:     Option<PackageLoader>,
     game_results:       Option<GameResults>,
+    set:                Option<Set>, // Kept outside of MenuState so that it survives playing a game
     netplay_history:    Vec<NetplayHistory>,
     prev_fighters_len:  usize,
     prev_stages_len:    usize,
@@ -50,6 +64,7 @@ pub struct NetplayHistory {
     prev_state:         Option<MenuState>,
     fighter_selections: Vec<PlayerSelect>,
     stage_ticker:       Option<MenuTicker>,
+    set:                Option<Set>,
 }
 
 pub enum PackageHolder {
@@ -102,6 +117,7 @@ impl Menu {
             game_setup:         None,
             package_loader:     None,
             game_results:       None,
+            set:                None,
             netplay_history:    vec!(),
             prev_fighters_len:  0,
             prev_stages_len:    0,
@@ -117,6 +133,10 @@ impl Menu {
                 self.state = MenuState::NetplayWait { message };
             }
             ResumeMenu::Results (results) => {
+                if let Some(ref mut set) = self.set {
+                    let package = self.package.get();
+                    set.record_game(&package.rules, package, &results.player_results);
+                }
                 self.game_results = Some(results);
                 self.prev_state = Some(mem::replace(&mut self.state, MenuState::game_results()));
             }
@@ -174,21 +194,13 @@ impl Menu {
                 ticker.reset();
             }
 
-            if (player_inputs.iter().any(|x| x.start.press || x.a.press)) && replays.len() > 0 {
+            // A plays the replay with the current package data, X with the package data it was recorded with
+            let use_snapshot = player_inputs.iter().any(|x| x.x.press);
+            if (player_inputs.iter().any(|x| x.start.press || x.a.press) || use_snapshot) && replays.len() > 0 {
                 let name = &replays[ticker.cursor];
                 match replays::load_replay(name, self.package.get()) {
                     Ok(replay) => {
-                        self.game_setup = Some(GameSetup {
-                            init_seed:      replay.init_seed,
-                            input_history:  replay.input_history,
-                            player_history: replay.player_history,
-                            stage_history:  replay.stage_history,
-                            controllers:    replay.selected_controllers,
-                            players:        replay.selected_players,
-                            ais:            replay.selected_ais,
-                            stage:          replay.selected_stage,
-                            state:          GameState::ReplayForwards,
-                        });
+                        self.game_setup = Some(replay.into_game_setup(use_snapshot));
                     }
                     Err(error) => {
                         println!("Failed to load replay: {}\n{}", name, error);
@@ -231,6 +243,7 @@ impl Menu {
                     cpu_ai:          None,
                     ui:              ui,
                     animation_frame: 0,
+                    handicap:        Handicap::default(),
                     team
                 });
             }
@@ -280,6 +293,9 @@ impl Menu {
                 }
             }
 
+            // the set tracks wins by team, so teams cannot change until the set is over
+            let teams_locked = self.set.is_some();
+
             for (controller_i, ref input) in player_inputs.iter().enumerate() {
                 if !input.plugged_in {
                     continue;
@@ -360,10 +376,12 @@ impl Menu {
                             PlayerSelectUi::CpuFighter (_) => {
                                 selection.fighter = None;
                             }
-                            PlayerSelectUi::HumanTeam (_) => {
+                            PlayerSelectUi::HumanTeam (_) |
+                            PlayerSelectUi::HumanHandicap (_) => {
                                 selection.ui = PlayerSelectUi::human_fighter(self.package.get());
                             }
                             PlayerSelectUi::CpuTeam (_) |
+                            PlayerSelectUi::CpuHandicap (_) |
                             PlayerSelectUi::CpuAi (_) => {
                                 selection.ui = PlayerSelectUi::cpu_fighter(self.package.get());
                                 selection.ui = PlayerSelectUi::cpu_fighter(self.package.get());
@@ -381,7 +399,8 @@ impl Menu {
                                 else {
                                     match ticker.cursor - fighters.len() {
                                         0 => { selection.ui = PlayerSelectUi::human_team() }
-                                        1 => { add_cpu = true; }
+                                        1 => { selection.ui = PlayerSelectUi::human_handicap() }
+                                        2 => { add_cpu = true; }
                                         _ => { unreachable!() }
                                     }
                                 }
@@ -394,8 +413,9 @@ impl Menu {
                                 else {
                                     match ticker.cursor - fighters.len() {
                                         0 => { selection.ui = PlayerSelectUi::cpu_team() }
-                                        1 => { /* TODO: selection.ui = PlayerSelectUi::cpu_ai()*/ }
-                                        2 => { remove_cpu = Some(selection_i); }
+                                        1 => { selection.ui = PlayerSelectUi::cpu_handicap() }
+                                        2 => { /* TODO: selection.ui = PlayerSelectUi::cpu_ai()*/ }
+                                        3 => { remove_cpu = Some(selection_i); }
                                         _ => { unreachable!() }
                                     }
                                 }
@@ -403,7 +423,9 @@ impl Menu {
                             PlayerSelectUi::HumanTeam (ticker) => {
                                 let colors = graphics::get_colors();
                                 if ticker.cursor < colors.len() {
-                                    selection.team = ticker.cursor;
+                                    if !teams_locked {
+                                        selection.team = ticker.cursor;
+                                    }
                                 } else {
                                     match ticker.cursor - colors.len() {
                                         0 => { selection.ui = PlayerSelectUi::human_fighter(self.package.get()) }
@@ -414,7 +436,9 @@ impl Menu {
                             PlayerSelectUi::CpuTeam (ticker) => {
                                 let colors = graphics::get_colors();
                                 if ticker.cursor < colors.len() {
-                                    selection.team = ticker.cursor;
+                                    if !teams_locked {
+                                        selection.team = ticker.cursor;
+                                    }
                                 } else {
                                     match ticker.cursor - colors.len() {
                                         0 => { selection.ui = PlayerSelectUi::cpu_fighter(self.package.get()) }
@@ -422,17 +446,37 @@ impl Menu {
                                     }
                                 }
                             }
+                            PlayerSelectUi::HumanHandicap (ticker) |
+                            PlayerSelectUi::CpuHandicap (ticker) => {
+                                let handicap = &mut selection.handicap;
+                                match ticker.cursor {
+                                    0 => { handicap.damage               = next_handicap_value(&HANDICAP_DAMAGE, handicap.damage) }
+                                    1 => { handicap.damage_taken_mult    = next_handicap_value(&HANDICAP_MULTS, handicap.damage_taken_mult) }
+                                    2 => { handicap.knockback_dealt_mult = next_handicap_value(&HANDICAP_MULTS, handicap.knockback_dealt_mult) }
+                                    3 => { handicap.extra_stocks         = next_handicap_value(&HANDICAP_EXTRA_STOCKS, handicap.extra_stocks) }
+                                    4 => {
+                                        selection.ui = if selection.ui.is_cpu() {
+                                            PlayerSelectUi::cpu_fighter(self.package.get())
+                                        } else {
+                                            PlayerSelectUi::human_fighter(self.package.get())
+                                        };
+                                    }
+                                    _ => { unreachable!() }
+                                }
+                            }
                             PlayerSelectUi::CpuAi (_) => { }
                             PlayerSelectUi::HumanUnplugged => unreachable!(),
                         }
                     }
 
                     match selection.ui {
-                        PlayerSelectUi::HumanFighter (ref mut ticker) |
-                        PlayerSelectUi::CpuFighter   (ref mut ticker) |
-                        PlayerSelectUi::HumanTeam    (ref mut ticker) |
-                        PlayerSelectUi::CpuTeam      (ref mut ticker) |
-                        PlayerSelectUi::CpuAi        (ref mut ticker) => {
+                        PlayerSelectUi::HumanFighter  (ref mut ticker) |
+                        PlayerSelectUi::CpuFighter    (ref mut ticker) |
+                        PlayerSelectUi::HumanTeam     (ref mut ticker) |
+                        PlayerSelectUi::CpuTeam       (ref mut ticker) |
+                        PlayerSelectUi::HumanHandicap (ref mut ticker) |
+                        PlayerSelectUi::CpuHandicap   (ref mut ticker) |
+                        PlayerSelectUi::CpuAi         (ref mut ticker) => {
                             if input[0].stick_y > 0.4 || input[0].up {
                                 ticker.up();
                             }
@@ -456,7 +500,7 @@ impl Menu {
             }
 
             if add_cpu {
-                if self.fighter_selections.iter().filter(|x| x.ui.is_visible()).count() < 4 {
+                if self.fighter_selections.iter().filter(|x| x.ui.is_visible()).count() < MAX_PLAYERS {
                     let team = Menu::get_free_team(&self.fighter_selections);
                     self.fighter_selections.push(PlayerSelect {
                         controller:      None,
@@ -464,20 +508,35 @@ impl Menu {
                         cpu_ai:          None,
                         ui:              PlayerSelectUi::cpu_fighter(self.package.get()),
                         animation_frame: 0,
+                        handicap:        Handicap::default(),
                         team
                     });
                 }
             }
 
-            if player_inputs.iter().any(|x| x.start.press) && fighters.len() > 0 {
-                new_state = Some(MenuState::StageSelect);
-                if let None = self.stage_ticker {
-                    self.stage_ticker = Some(MenuTicker::new(self.package.get().stages.len()));
+            let too_many_players = self.fighter_selections.iter().filter(|x| x.ui.is_visible()).count() > MAX_PLAYERS;
+            if player_inputs.iter().any(|x| x.start.press) && fighters.len() > 0 && !too_many_players {
+                let package = self.package.get();
+                if package.rules.best_of > 1 {
+                    // players joining or leaving changes the teams, which starts a new set
+                    let teams = Menu::playing_teams(&self.fighter_selections);
+                    let same_teams = self.set.as_ref().map_or(false, |set| set.teams.len() == teams.len() && teams.iter().all(|x| set.teams.contains(x)));
+                    if !same_teams {
+                        self.set = Some(Set::new(&package.rules, package, teams));
+                    }
+                    new_state = Some(MenuState::set_stage_select(self.set.as_ref().unwrap()));
+                }
+                else {
+                    new_state = Some(MenuState::StageSelect);
+                    if let None = self.stage_ticker {
+                        self.stage_ticker = Some(MenuTicker::new(package.stages.len()));
+                    }
                 }
             }
             else if player_inputs.iter().any(|x| x[0].b) {
                 if *back_counter > self.back_counter_max {
                     netplay.set_offline();
+                    self.set = None;
                     new_state = Some(MenuState::GameSelect);
                 }
                 else {
@@ -494,6 +553,17 @@ impl Menu {
         }
     }
 
+    /// Teams of the selections that will play in the next game, in selection order
+    fn playing_teams(selections: &[PlayerSelect]) -> Vec<usize> {
+        let mut teams = vec!();
+        for selection in selections {
+            if (selection.ui.is_human_plugged_in() || selection.ui.is_cpu()) && selection.fighter.is_some() && !teams.contains(&selection.team) {
+                teams.push(selection.team);
+            }
+        }
+        teams
+    }
+
     fn get_free_team(selections: &[PlayerSelect]) -> usize {
         let mut team = 0;
         while selections.iter().any(|x| x.ui.is_visible() && x.team == team) {
@@ -521,14 +591,61 @@ impl Menu {
         }
 
         if (player_inputs.iter().any(|x| x.start.press || x.a.press)) && self.package.get().stages.len() > 0 {
-            self.game_setup(netplay);
+            let stage = self.package.get().stages.index_to_key(ticker.cursor).unwrap();
+            self.game_setup(netplay, stage);
+        }
+        else if player_inputs.iter().any(|x| x.b.press) {
+            self.state = MenuState::character_select();
+        }
+    }
+
+    fn step_set_stage_select(&mut self, player_inputs: &[PlayerInput], netplay: &Netplay) {
+        let mut choice = None;
+        if let &mut MenuState::SetStageSelect (ref mut ticker) = &mut self.state {
+            let set = self.set.as_ref().unwrap();
+
+            // only the team whose turn it is can choose, unless none of its players are human
+            let turn_team = set.current_turn().map(|x| x.team);
+            let mut team_inputs: Vec<&PlayerInput> = vec!();
+            for (i, selection) in self.fighter_selections.iter().enumerate() {
+                if selection.ui.is_human_plugged_in() && Some(selection.team) == turn_team {
+                    if let Some(input) = player_inputs.get(i) {
+                        team_inputs.push(input);
+                    }
+                }
+            }
+            if team_inputs.len() == 0 {
+                team_inputs = player_inputs.iter().collect();
+            }
+
+            if team_inputs.iter().any(|x| x[0].stick_y > 0.4 || x[0].up) {
+                ticker.up();
+            }
+            else if team_inputs.iter().any(|x| x[0].stick_y < -0.4 || x[0].down) {
+                ticker.down();
+            }
+            else {
+                ticker.reset();
+            }
+
+            if team_inputs.iter().any(|x| x.a.press) {
+                choice = Some(ticker.cursor);
+            }
+        } else { unreachable!(); }
+
+        if let Some(stage_i) = choice {
+            self.set.as_mut().unwrap().choose(stage_i);
+        }
+
+        if let Some(stage) = self.set.as_ref().unwrap().picked.clone() {
+            self.game_setup(netplay, stage);
         }
         else if player_inputs.iter().any(|x| x.b.press) {
             self.state = MenuState::character_select();
         }
     }
 
-    pub fn game_setup(&mut self, netplay: &Netplay) {
+    pub fn game_setup(&mut self, netplay: &Netplay, stage: String) {
         let mut players: Vec<PlayerSetup> = vec!();
         let mut controllers: Vec<usize> = vec!();
         let mut ais: Vec<usize> = vec!();
@@ -538,8 +655,9 @@ impl Menu {
             if selection.ui.is_human_plugged_in() {
                 if let Some(fighter) = selection.fighter {
                     players.push(PlayerSetup {
-                        fighter: self.package.get().fighters.index_to_key(fighter).unwrap(),
-                        team:    selection.team,
+                        fighter:  self.package.get().fighters.index_to_key(fighter).unwrap(),
+                        team:     selection.team,
+                        handicap: selection.handicap.clone(),
                     });
                     controllers.push(i);
                 }
@@ -550,8 +668,9 @@ impl Menu {
                 if selection.fighter.is_some() /* && selection.cpu.is_some() TODO */ {
                     let fighter = selection.fighter.unwrap();
                     players.push(PlayerSetup {
-                        fighter: self.package.get().fighters.index_to_key(fighter).unwrap(),
-                        team:    selection.team,
+                        fighter:  self.package.get().fighters.index_to_key(fighter).unwrap(),
+                        team:     selection.team,
+                        handicap: selection.handicap.clone(),
                     });
                     controllers.push(i - ais_skipped);
                     ais.push(0); // TODO: delete this
@@ -563,14 +682,17 @@ impl Menu {
             }
         }
 
-        let stage = self.package.get().stages.index_to_key(self.stage_ticker.as_ref().unwrap().cursor).unwrap();
         let state = if netplay.number_of_peers() == 1 { GameState::Local } else { GameState::Netplay };
         let init_seed = netplay.get_seed().unwrap_or(GameSetup::gen_seed());
 
         self.game_setup = Some(GameSetup {
-            input_history:  vec!(),
-            player_history: vec!(),
-            stage_history:  vec!(),
+            input_history:      vec!(),
+            player_history:     PlayerHistory::default(),
+            stage_history:      vec!(),
+            projectile_history: vec!(),
+            checksums:          vec!(),
+            sudden_death:       None,
+            snapshot:           None,
             init_seed,
             controllers,
             ais,
@@ -632,6 +754,7 @@ impl Menu {
         self.state = MenuState::GameSelect;
         self.fighter_selections = vec!();
         self.stage_ticker = None;
+        self.set = None;
 
         // remember selection
         self.config.current_package = Some(self.package.get().meta.folder_name());
@@ -685,7 +808,12 @@ impl Menu {
 
     fn step_results(&mut self, player_inputs: &[PlayerInput]) {
         if player_inputs.iter().any(|x| x.start.press || x.a.press) {
-            self.state = self.prev_state.take().unwrap();
+            let prev_state = self.prev_state.take().unwrap();
+            self.state = match self.set {
+                Some(ref set) if set.is_finished() => MenuState::SetSummary,
+                Some(_)                            => MenuState::character_select(), // fighters can be changed between games
+                None                               => prev_state,
+            };
         }
 
         // TODO:
@@ -696,13 +824,20 @@ impl Menu {
         if let &mut MenuState::GameResults { ref mut replay_saved, .. } = &mut self.state {
             if !*replay_saved {
                 if self.config.auto_save_replay || player_inputs.iter().any(|x| x.l.press && x.r.press) {
-                    replays::save_replay(&self.game_results.as_ref().unwrap().replay, self.package.get());
+                    replays::save_replay(&self.game_results.as_ref().unwrap().replay, self.package.get(), &self.config);
                     *replay_saved = true;
                 }
             }
         }
     }
 
+    fn step_set_summary(&mut self, player_inputs: &[PlayerInput]) {
+        if player_inputs.iter().any(|x| x.start.press || x.a.press) {
+            self.set = None;
+            self.state = MenuState::character_select();
+        }
+    }
+
     fn step_netplay_wait(&mut self, player_inputs: &[PlayerInput], netplay: &mut Netplay) {
         if player_inputs.iter().any(|x| x.b.press) {
             self.state = MenuState::GameSelect;
@@ -780,6 +915,7 @@ impl Menu {
                 self.prev_state         = history.prev_state.clone();
                 self.fighter_selections = history.fighter_selections.clone();
                 self.stage_ticker       = history.stage_ticker.clone();
+                self.set                = history.set.clone();
             }
 
             input.netplay_update();
@@ -799,7 +935,9 @@ impl Menu {
                         MenuState::PackageSelect (_, _) => self.step_package_select(&player_inputs),
                         MenuState::CharacterSelect {..} => self.step_fighter_select(&player_inputs, netplay),
                         MenuState::StageSelect          => self.step_stage_select  (&player_inputs, netplay),
+                        MenuState::SetStageSelect (_)   => self.step_set_stage_select(&player_inputs, netplay),
                         MenuState::GameResults {..}     => self.step_results       (&player_inputs),
+                        MenuState::SetSummary           => self.step_set_summary   (&player_inputs),
                         MenuState::NetplayWait {..}     => self.step_netplay_wait  (&player_inputs, netplay),
                     };
                 }
@@ -809,6 +947,7 @@ impl Menu {
                     prev_state:         self.prev_state.clone(),
                     fighter_selections: self.fighter_selections.clone(),
                     stage_ticker:       self.stage_ticker.clone(),
+                    set:                self.set.clone(),
                 });
             }
         }
@@ -828,12 +967,14 @@ impl Menu {
                         self.package_loader.as_ref().map(|x| x.state.message()).unwrap_or_default()
                     )
                 }
-                MenuState::GameResults { replay_saved } => RenderMenuState::GameResults { results: self.game_results.as_ref().unwrap().player_results.clone(), replay_saved },
+                MenuState::GameResults { replay_saved } => RenderMenuState::GameResults { results: self.game_results.as_ref().unwrap().player_results.clone(), replay_saved, set: self.set.clone() },
                 MenuState::CharacterSelect { back_counter, .. } => RenderMenuState::CharacterSelect (self.fighter_selections.clone(), back_counter, self.back_counter_max),
                 MenuState::ReplaySelect (ref replays, ref ticker) => RenderMenuState::ReplaySelect (replays.clone(), ticker.cursor),
                 MenuState::NetplayWait { ref message } => RenderMenuState::GenericText (message.clone()),
                 MenuState::GameSelect  => RenderMenuState::GameSelect  (self.game_ticker.cursor),
                 MenuState::StageSelect => RenderMenuState::StageSelect (self.stage_ticker.as_ref().unwrap().cursor),
+                MenuState::SetStageSelect (ref ticker) => RenderMenuState::SetStageSelect (self.set.clone().unwrap(), ticker.cursor),
+                MenuState::SetSummary => RenderMenuState::SetSummary (self.set.clone().unwrap()),
             },
             package_verify: self.package.verify(),
         }
@@ -931,7 +1072,9 @@ pub enum MenuState {
     ReplaySelect (Vec<String>, MenuTicker), // MenuTicker must be tied with the Vec<String>, otherwise they may become out of sync
     CharacterSelect { back_counter: usize },
     StageSelect,
+    SetStageSelect (MenuTicker), // The stages are stored in Menu::set
     GameResults { replay_saved: bool },
+    SetSummary,
     PackageSelect (Vec<(String, PackageMeta)>, MenuTicker),
     NetplayWait { message: String },
 }
@@ -953,6 +1096,10 @@ impl MenuState {
         MenuState::CharacterSelect { back_counter: 0 }
     }
 
+    pub fn set_stage_select(set: &Set) -> MenuState {
+        MenuState::SetStageSelect (MenuTicker::new(set.stages.len()))
+    }
+
     pub fn game_results() -> MenuState {
         MenuState::GameResults { replay_saved: false }
     }
@@ -996,7 +1143,9 @@ pub enum RenderMenuState {
     ReplaySelect    (Vec<String>, usize),
     CharacterSelect (Vec<PlayerSelect>, usize, usize),
     StageSelect     (usize),
-    GameResults     { results: Vec<PlayerResult>, replay_saved: bool },
+    SetStageSelect  (Set, usize),
+    GameResults     { results: Vec<PlayerResult>, replay_saved: bool, set: Option<Set> },
+    SetSummary      (Set),
     PackageSelect   (Vec<String>, usize, String),
     GenericText     (String),
 }
@@ -1009,6 +1158,7 @@ pub struct PlayerSelect {
     pub team:            usize,
     pub ui:              PlayerSelectUi,
     pub animation_frame: usize,
+    pub handicap:        Handicap,
 }
 
 impl PlayerSelect {
@@ -1021,11 +1171,13 @@ impl PlayerSelect {
 #[derive(Clone)]
 #[allow(dead_code)]
 pub enum PlayerSelectUi {
-    CpuAi        (MenuTicker),
-    CpuFighter   (MenuTicker),
-    CpuTeam      (MenuTicker),
-    HumanFighter (MenuTicker),
-    HumanTeam    (MenuTicker),
+    CpuAi         (MenuTicker),
+    CpuFighter    (MenuTicker),
+    CpuTeam       (MenuTicker),
+    CpuHandicap   (MenuTicker),
+    HumanFighter  (MenuTicker),
+    HumanTeam     (MenuTicker),
+    HumanHandicap (MenuTicker),
     HumanUnplugged,
 }
 
@@ -1036,11 +1188,11 @@ impl PlayerSelectUi {
     }
 
     pub fn cpu_fighter(package: &Package) -> Self {
-        PlayerSelectUi::CpuFighter (MenuTicker::new(package.fighters.len() + 3))
+        PlayerSelectUi::CpuFighter (MenuTicker::new(package.fighters.len() + 4))
     }
 
     pub fn human_fighter(package: &Package) -> Self {
-        PlayerSelectUi::HumanFighter (MenuTicker::new(package.fighters.len() + 2))
+        PlayerSelectUi::HumanFighter (MenuTicker::new(package.fighters.len() + 3))
     }
 
     pub fn cpu_team() -> Self {
@@ -1051,6 +1203,14 @@ impl PlayerSelectUi {
         PlayerSelectUi::HumanTeam (MenuTicker::new(graphics::get_colors().len() + 1))
     }
 
+    pub fn cpu_handicap() -> Self {
+        PlayerSelectUi::CpuHandicap (MenuTicker::new(5))
+    }
+
+    pub fn human_handicap() -> Self {
+        PlayerSelectUi::HumanHandicap (MenuTicker::new(5))
+    }
+
     pub fn is_visible(&self) -> bool {
         match self {
             &PlayerSelectUi::HumanUnplugged => false,
@@ -1062,27 +1222,31 @@ impl PlayerSelectUi {
         match self {
             &PlayerSelectUi::CpuAi (_) |
             &PlayerSelectUi::CpuFighter (_) |
-            &PlayerSelectUi::CpuTeam (_) => true,
-            _                            => false
+            &PlayerSelectUi::CpuTeam (_) |
+            &PlayerSelectUi::CpuHandicap (_) => true,
+            _                                => false
         }
     }
 
     pub fn is_human_plugged_in(&self) -> bool {
         match self {
             &PlayerSelectUi::HumanFighter (_) |
-            &PlayerSelectUi::HumanTeam (_) => true,
-            _                              => false
+            &PlayerSelectUi::HumanTeam (_) |
+            &PlayerSelectUi::HumanHandicap (_) => true,
+            _                                  => false
         }
     }
 
     #[allow(dead_code)] // Needed for headless build
     pub fn ticker_unwrap(&self) -> &MenuTicker {
         match self {
-            &PlayerSelectUi::HumanFighter (ref ticker) |
-            &PlayerSelectUi::CpuFighter   (ref ticker) |
-            &PlayerSelectUi::HumanTeam    (ref ticker) |
-            &PlayerSelectUi::CpuTeam      (ref ticker) |
-            &PlayerSelectUi::CpuAi        (ref ticker) => { ticker }
+            &PlayerSelectUi::HumanFighter  (ref ticker) |
+            &PlayerSelectUi::CpuFighter    (ref ticker) |
+            &PlayerSelectUi::HumanTeam     (ref ticker) |
+            &PlayerSelectUi::CpuTeam       (ref ticker) |
+            &PlayerSelectUi::HumanHandicap (ref ticker) |
+            &PlayerSelectUi::CpuHandicap   (ref ticker) |
+            &PlayerSelectUi::CpuAi         (ref ticker) => { ticker }
             &PlayerSelectUi::HumanUnplugged => {
                 panic!("Tried to unwrap the PlayerSelectUi ticker but was HumanUnplugged")
             }
@@ -1091,11 +1255,13 @@ impl PlayerSelectUi {
 
     pub fn ticker_full_reset(&mut self) {
         match self {
-            &mut PlayerSelectUi::HumanFighter (ref mut ticker) |
-            &mut PlayerSelectUi::CpuFighter   (ref mut ticker) |
-            &mut PlayerSelectUi::HumanTeam    (ref mut ticker) |
-            &mut PlayerSelectUi::CpuTeam      (ref mut ticker) |
-            &mut PlayerSelectUi::CpuAi        (ref mut ticker) => {
+            &mut PlayerSelectUi::HumanFighter  (ref mut ticker) |
+            &mut PlayerSelectUi::CpuFighter    (ref mut ticker) |
+            &mut PlayerSelectUi::HumanTeam     (ref mut ticker) |
+            &mut PlayerSelectUi::CpuTeam       (ref mut ticker) |
+            &mut PlayerSelectUi::HumanHandicap (ref mut ticker) |
+            &mut PlayerSelectUi::CpuHandicap   (ref mut ticker) |
+            &mut PlayerSelectUi::CpuAi         (ref mut ticker) => {
                 ticker.reset();
                 ticker.cursor = 0;
             }
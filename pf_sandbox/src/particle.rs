@@ -1,3 +1,5 @@
+use pf_sandbox_lib::fighter::HitboxEffect;
+
 use treeflection::{Node, NodeRunner, NodeToken};
 
 #[derive(Clone, Default, Serialize, Deserialize)]
@@ -22,6 +24,7 @@ pub enum ParticleType {
     AirJump,
     Hit { knockback: f32, damage: f32 },
     Spark { x_vel: f32, y_vel: f32, size: f32, angle_vel: f32, background: bool },
+    Effect (HitboxEffect),
 }
 
 impl Default for ParticleType {
@@ -40,6 +43,16 @@ impl Particle {
                 self.y += y_vel;
                 self.angle += angle_vel;
             }
+            ParticleType::Effect (HitboxEffect::Fire) => {
+                self.y += 0.4;
+            }
+            ParticleType::Effect (HitboxEffect::Sleep { .. }) => {
+                self.y += 0.15;
+                self.x += (self.counter as f32 * 0.2).sin() * 0.2;
+            }
+            ParticleType::Effect (HitboxEffect::Stun { .. }) => {
+                self.angle += 0.2;
+            }
             _ => { }
         }
         self.counter > self.counter_max
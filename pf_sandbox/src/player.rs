@@ -1,4 +1,5 @@
 use crate::collision::CollisionResult;
+use crate::game::Handicap;
 use crate::graphics;
 use crate::particle::{Particle, ParticleType};
 use crate::results::{RawPlayerResult, DeathRecord};
@@ -8,8 +9,8 @@ use pf_sandbox_lib::geometry::Rect;
 use pf_sandbox_lib::geometry;
 use pf_sandbox_lib::input::{PlayerInput};
 use pf_sandbox_lib::package::Package;
-use pf_sandbox_lib::rules::Goal;
-use pf_sandbox_lib::stage::{Stage, Surface};
+use pf_sandbox_lib::rules::{Goal, Rules, Teams};
+use pf_sandbox_lib::stage::{connected_floors, Floor, Stage, Surface};
 
 use treeflection::{Node, NodeRunner, NodeToken, KeyedContextVec};
 use rand::Rng;
@@ -20,9 +21,10 @@ use winit_input_helper::WinitInputHelper;
 
 use std::f32;
 use std::f32::consts::PI;
-use std::collections::HashSet;
+use std::collections::{HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
 
-#[derive(Clone, Debug, Serialize, Deserialize, Node)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Node)]
 pub enum LockTimer {
     Active (u64),
     Locked (u64),
@@ -44,7 +46,7 @@ impl Default for LockTimer {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Node)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Node)]
 pub enum LedgeLogic {
     Hog,
     Share,
@@ -52,7 +54,7 @@ pub enum LedgeLogic {
 }
 
 // Describes the player location by offsets from other locations
-#[derive(Debug, Clone, Serialize, Deserialize, Node)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Node)]
 pub enum Location {
     Surface { platform_i: usize, x: f32 },
     GrabbedLedge { platform_i: usize, d_x: f32, d_y: f32, logic: LedgeLogic }, // player.face_right determines which edge on the platform
@@ -72,7 +74,7 @@ impl Default for LedgeLogic {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Node)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Node)]
 pub enum Hitlag {
     Some (u64), // TODO: rename Some
     Launch { counter: u64, kb_vel: f32, angle: f32, wobble_x: f32 },
@@ -117,6 +119,7 @@ pub struct StepContext<'a> {
     pub fighter:  &'a Fighter,
     pub stage:    &'a Stage,
     pub surfaces: &'a [Surface],
+    pub rules:    &'a Rules,
     pub rng:      &'a mut ChaChaRng,
 }
 
@@ -124,6 +127,7 @@ pub struct StepContext<'a> {
 pub struct Player {
     pub fighter:            String,
     pub team:               usize,
+    pub handicap:           Handicap,
     pub action:             u64, // always change through next_action
     pub set_action_called:  bool,
     pub new_action:         bool,
@@ -164,7 +168,15 @@ pub struct Player {
     pub hitlag:             Hitlag,
     pub hitstun:            f32,
     pub hit_by:             Option<usize>,
+    pub hit_by_timer:       u64, // frames since the player was last hit by hit_by
+    pub grabbing:           Option<usize>, // index of the player held by this player
+    pub hazard_timer:       u64, // frames until the player can be hit by a stage hazard again
+    pub stale_moves:        Vec<u64>, // actions that recently landed a hit, newest first
+    pub intangible_timer:   u64,
+    pub ledge_grabs:        u64, // ledge grabs since last landing or being hit
+    pub grab_timer:         u64, // frames until a held player breaks free, reduced faster by mashing
     pub particles:          Vec<Particle>,
+    pub projectile_spawns:  Vec<ProjectileSpawn>, // projectiles to be spawned by the game this frame
     pub aerial_dodge_frame: Option<u64>,
     pub result:             RawPlayerResult,
 
@@ -172,18 +184,16 @@ pub struct Player {
     pub frames_since_hit:  u64,
     pub hit_angle_pre_di:  Option<f32>,
     pub hit_angle_post_di: Option<f32>,
+    pub sdi_offset:        Option<(f32, f32)>, // total distance moved by smash DI during the last hitlag
+    pub asdi_offset:       Option<(f32, f32)>,
     pub stick:             Option<(f32, f32)>,
     pub c_stick:           Option<(f32, f32)>,
 }
 
 impl Player {
-    pub fn new(fighter: String, team: usize, player_i: usize, stage: &Stage, package: &Package) -> Player {
+    pub fn new(fighter: String, team: usize, handicap: Handicap, player_i: usize, stage: &Stage, package: &Package) -> Player {
         // get the spawn point
-        let spawn = if stage.spawn_points.len() == 0 {
-            None
-        } else {
-            Some(stage.spawn_points[player_i % stage.spawn_points.len()].clone())
-        };
+        let spawn = stage.spawn_point(player_i);
 
         let location = if let Some(spawn) = &spawn {
             // find the floor directly beneath the player
@@ -192,7 +202,8 @@ impl Player {
                 world_y: f32, // The y coordinate of the point on the floor corresponding to the spawnpoints x coordinate.
             }
             let mut found_floor = None;
-            for (surface_i, surface) in stage.surfaces.iter().enumerate() {
+            let surfaces = stage.surfaces_at(0);
+            for (surface_i, surface) in surfaces.iter().enumerate() {
                 let spawn_x_in_bounds = surface.world_x_in_bounds(spawn.x);
                 let world_y = surface.world_x_to_world_y(spawn.x);
                 let above_plat = world_y <= spawn.y;
@@ -204,7 +215,7 @@ impl Player {
             }
 
             if let Some(floor) = found_floor {
-                let surface = &stage.surfaces[floor.surface_i];
+                let surface = &surfaces[floor.surface_i];
                 // place the player on the platform
                 Location::Surface { platform_i: floor.surface_i, x: surface.world_x_to_plat_x(spawn.x) }
             } else {
@@ -225,8 +236,8 @@ impl Player {
             new_action:         false,
             frame:              0,
             frame_norestart:    0,
-            stocks:             package.rules.stock_count,
-            damage:             0.0,
+            stocks:             package.rules.stock_count.map(|x| x + handicap.extra_stocks),
+            damage:             handicap.damage,
             x_vel:              0.0,
             y_vel:              0.0,
             kb_x_vel:           0.0,
@@ -254,22 +265,147 @@ impl Player {
             hitlag:             Hitlag::None,
             hitstun:            0.0,
             hit_by:             None,
+            hit_by_timer:       0,
+            grabbing:           None,
+            hazard_timer:       0,
+            stale_moves:        vec!(),
+            intangible_timer:   0,
+            ledge_grabs:        0,
+            grab_timer:         0,
             particles:          vec!(),
+            projectile_spawns:  vec!(),
             aerial_dodge_frame: None,
             result:             RawPlayerResult::default(),
             team,
             fighter,
+            handicap,
             location,
 
             // Only use for debug display
             frames_since_hit:  0,
             hit_angle_pre_di:  None,
             hit_angle_post_di: None,
+            sdi_offset:        None,
+            asdi_offset:       None,
             stick:             None,
             c_stick:           None,
         }
     }
 
+    /// Feeds the state that affects the simulation into the hasher, particles and the debug display fields are left out.
+    /// Floats are hashed by their bits, so only identical simulations match.
+    pub fn hash_state<H: Hasher>(&self, hasher: &mut H) {
+        self.fighter.hash(hasher);
+        hasher.write_u64(self.action);
+        hasher.write_u8(self.set_action_called as u8);
+        hasher.write_u8(self.new_action as u8);
+        hasher.write_i64(self.frame);
+        hasher.write_i64(self.frame_norestart);
+        self.stocks.hash(hasher);
+        hasher.write_u32(self.damage.to_bits());
+
+        match self.location {
+            Location::Surface { platform_i, x } => {
+                hasher.write_u8(0);
+                hasher.write_usize(platform_i);
+                hasher.write_u32(x.to_bits());
+            }
+            Location::GrabbedLedge { platform_i, d_x, d_y, ref logic } => {
+                hasher.write_u8(1);
+                hasher.write_usize(platform_i);
+                hasher.write_u32(d_x.to_bits());
+                hasher.write_u32(d_y.to_bits());
+                hasher.write_u8(logic.clone() as u8);
+            }
+            Location::GrabbedByPlayer (player_i) => {
+                hasher.write_u8(2);
+                hasher.write_usize(player_i);
+            }
+            Location::Airbourne { x, y } => {
+                hasher.write_u8(3);
+                hasher.write_u32(x.to_bits());
+                hasher.write_u32(y.to_bits());
+            }
+        }
+
+        for value in &[self.x_vel, self.y_vel, self.kb_x_vel, self.kb_y_vel, self.kb_x_dec, self.kb_y_dec] {
+            hasher.write_u32(value.to_bits());
+        }
+        hasher.write_u8(self.face_right as u8);
+        hasher.write_u64(self.frames_since_ledge);
+        hasher.write_u64(self.ledge_idle_timer);
+        hasher.write_u8(self.fastfalled as u8);
+        hasher.write_u64(self.air_jumps_left);
+        hasher.write_u8(self.jumpsquat_button as u8);
+        for value in &[self.shield_hp, self.shield_analog, self.shield_offset_x, self.shield_offset_y] {
+            hasher.write_u32(value.to_bits());
+        }
+        hasher.write_u64(self.stun_timer);
+        hasher.write_u64(self.shield_stun_timer);
+        hasher.write_u64(self.parry_timer);
+
+        match self.tech_timer {
+            LockTimer::Active (timer) => { hasher.write_u8(0); hasher.write_u64(timer) }
+            LockTimer::Locked (timer) => { hasher.write_u8(1); hasher.write_u64(timer) }
+            LockTimer::Free           => { hasher.write_u8(2) }
+        }
+
+        hasher.write_u64(self.lcancel_timer);
+        hasher.write_u8(self.land_frame_skip);
+        for value in &[self.ecb.left, self.ecb.right, self.ecb.top, self.ecb.bottom] {
+            hasher.write_u32(value.to_bits());
+        }
+        hasher.write_usize(self.hitlist.len());
+        for player_i in &self.hitlist {
+            hasher.write_usize(*player_i);
+        }
+
+        match self.hitlag {
+            Hitlag::Some (counter) => {
+                hasher.write_u8(0);
+                hasher.write_u64(counter);
+            }
+            Hitlag::Launch { counter, kb_vel, angle, wobble_x } => {
+                hasher.write_u8(1);
+                hasher.write_u64(counter);
+                hasher.write_u32(kb_vel.to_bits());
+                hasher.write_u32(angle.to_bits());
+                hasher.write_u32(wobble_x.to_bits());
+            }
+            Hitlag::None => {
+                hasher.write_u8(2);
+            }
+        }
+
+        hasher.write_u32(self.hitstun.to_bits());
+        self.hit_by.hash(hasher);
+        hasher.write_u64(self.hit_by_timer);
+        self.grabbing.hash(hasher);
+        hasher.write_u64(self.hazard_timer);
+        hasher.write_usize(self.stale_moves.len());
+        for action in &self.stale_moves {
+            hasher.write_u64(*action);
+        }
+        hasher.write_u64(self.intangible_timer);
+        hasher.write_u64(self.ledge_grabs);
+        hasher.write_u64(self.grab_timer);
+        hasher.write_usize(self.projectile_spawns.len());
+        for spawn in &self.projectile_spawns {
+            hasher.write_usize(spawn.projectile);
+            hasher.write_u32(spawn.x.to_bits());
+            hasher.write_u32(spawn.y.to_bits());
+        }
+        self.aerial_dodge_frame.hash(hasher);
+
+        hasher.write_usize(self.result.deaths.len());
+        for death in &self.result.deaths {
+            death.player.hash(hasher);
+            hasher.write_usize(death.frame);
+        }
+        hasher.write_u64(self.result.lcancel_attempts);
+        hasher.write_u64(self.result.lcancel_success);
+    }
+
     fn bps_xy(&self, context: &StepContext) -> (f32, f32) {
         self.public_bps_xy(&context.players, &context.fighters, &context.surfaces)
     }
@@ -368,6 +504,11 @@ impl Player {
         false
     }
 
+    /// cannot be hit by hitboxes, grabs or hazards
+    pub fn is_intangible(&self) -> bool {
+        self.intangible_timer > 0
+    }
+
     pub fn is_shielding(&self) -> bool {
         match Action::from_u64(self.action) {
             Some(Action::Shield) |
@@ -386,6 +527,9 @@ impl Player {
     }
 
     pub fn public_set_action(&mut self, action: Action) {
+        if !action.is_grabbing() {
+            self.grabbing = None;
+        }
         let action = action as u64;
         self.frame = 0;
         self.hitlist.clear();
@@ -396,6 +540,9 @@ impl Player {
     }
 
     fn set_action(&mut self, context: &mut StepContext, action: Action) {
+        if !action.is_grabbing() {
+            self.grabbing = None;
+        }
         let action = action as u64;
         self.frame = 0;
         self.hitlist.clear();
@@ -455,82 +602,27 @@ impl Player {
             match col_result {
                 &CollisionResult::HitAtk { player_def_i, ref hitbox, ref point } => {
                     self.hit_particles(point.clone(), hitbox);
+                    // only the first hit of the action is added to the queue
+                    if self.hitlist.is_empty() {
+                        let action = self.action;
+                        self.push_stale_move(context.rules, action);
+                    }
                     self.hitlist.push(player_def_i);
-                    self.hitlag = Hitlag::Some ((hitbox.damage / 3.0 + 3.0) as u64);
+                    self.hitlag = Hitlag::Some (context.rules.knockback.hitlag(hitbox.damage) as u64);
                 }
                 &CollisionResult::HitDef { ref hitbox, ref hurtbox, player_atk_i } => {
                     let player_atk = &context.players[player_atk_i];
-
-                    let damage_done = hitbox.damage * hurtbox.damage_mult; // TODO: apply staling
-                    self.damage += damage_done;
-
-                    let damage_launch = 0.05 * (hitbox.damage * (damage_done + self.damage.floor())) + (damage_done + self.damage) * 0.1;
-                    let weight = 2.0 - (2.0 * context.fighter.weight) / (1.0 + context.fighter.weight);
-                    let kbg = hitbox.kbg + hurtbox.kbg_add;
-                    let bkb = hitbox.bkb + hurtbox.bkb_add;
-
-                    let mut kb_vel = (bkb + kbg * (damage_launch * weight * 1.4 + 18.0)).min(2500.0);
-
-                    if let Some(action) = Action::from_u64(self.action) {
-                        match action {
-                            Action::Crouch => {
-                                kb_vel *= 0.67;
-                            }
-                            _ => { }
-                        }
-                    }
-
-                    if !self.is_grabbed() || kb_vel > 50.0 {
-                        self.hitstun = match hitbox.hitstun {
-                            HitStun::FramesTimesKnockback (frames) => { frames * kb_vel }
-                            HitStun::Frames               (frames) => { frames as f32 }
-                        };
-
-                        self.set_airbourne(context);
-
-                        if kb_vel > 80.0 {
-                            self.set_action(context, Action::DamageFly);
-                        }
-                        else {
-                            self.set_action(context, Action::Damage);
-                        }
-                    }
-
-                    // handle sakurai angle
-                    let angle_deg = if hitbox.angle == 361.0 {
-                        if kb_vel < 32.1 {
-                            0.0
-                        }
-                        else {
-                            44.0
-                        }
-                    } else if hitbox.angle == 180.0 - 361.0 {
-                        if kb_vel < 32.1 {
-                            180.0
-                        }
-                        else {
-                            180.0 - 44.0
-                        }
-                    } else {
-                        hitbox.angle
-                    };
-
-                    // convert from degrees to radians
-                    let angle_rad = angle_deg.to_radians() + if angle_deg < 0.0 { PI * 2.0 } else { 0.0 };
-
-                    // handle reverse hits
-                    let behind_player_atk = self.bps_xy(context).0 < player_atk.bps_xy(context).0 && player_atk.face_right ||
-                                            self.bps_xy(context).0 > player_atk.bps_xy(context).0 && !player_atk.face_right;
-                    let angle = if hitbox.enable_reverse_hit && behind_player_atk { PI - angle_rad } else { angle_rad };
-
-                    // debug data
-                    self.hit_angle_pre_di = Some(angle);
-                    self.hit_angle_post_di = None;
-                    self.frames_since_hit = 0;
-
-                    self.hitlag = Hitlag::Launch { counter: (hitbox.damage / 3.0 + 3.0) as u64, kb_vel, angle, wobble_x: 0.0 };
-                    self.hit_by = Some(player_atk_i);
-                    self.face_right = self.bps_xy(context).0 < player_atk.bps_xy(context).0;
+                    let atk_x = player_atk.bps_xy(context).0;
+                    let atk_face_right = player_atk.face_right;
+                    let hitbox = player_atk.stale_hitbox(context.rules, hitbox, player_atk.action);
+                    self.launch(context, &hitbox, hurtbox, Some(player_atk_i), atk_x, atk_face_right);
+                }
+                &CollisionResult::ProjectileHitDef { ref hitbox, ref hurtbox, player_atk_i, action, x, face_right } => {
+                    let hitbox = context.players[player_atk_i].stale_hitbox(context.rules, hitbox, action);
+                    self.launch(context, &hitbox, hurtbox, Some(player_atk_i), x, face_right);
+                }
+                &CollisionResult::ProjectileHitAtk { action } => {
+                    self.push_stale_move(context.rules, action);
                 }
                 &CollisionResult::HitShieldAtk { ref hitbox, ref power_shield, player_def_i} => {
                     self.hitlist.push(player_def_i);
@@ -547,35 +639,291 @@ impl Player {
                     if self.is_platform() {
                         self.x_vel += vel * x_diff.signum();
                     }
-                    self.hitlag = Hitlag::Some ((hitbox.damage / 3.0 + 3.0) as u64);
+                    self.hitlag = Hitlag::Some (context.rules.knockback.hitlag(hitbox.damage) as u64);
                 }
                 &CollisionResult::HitShieldDef { ref hitbox, ref power_shield, player_atk_i } => {
-                    if let &Some(ref power_shield) = power_shield {
-                        if let (Some(Action::PowerShield), &Some(ref parry)) = (Action::from_u64(self.action), &power_shield.parry) {
-                            if parry.window > self.frame as u64 {
-                                self.parry_timer = parry.duration;
-                            }
-                        }
-                    }
+                    let player_atk = &context.players[player_atk_i];
+                    let atk_x = player_atk.bps_xy(context).0;
+                    let hitbox = player_atk.stale_hitbox(context.rules, hitbox, player_atk.action);
+                    self.hit_shield(context, &hitbox, power_shield, atk_x);
+                }
+                &CollisionResult::ProjectileHitShieldDef { ref hitbox, ref power_shield, player_atk_i, action, x } => {
+                    let hitbox = context.players[player_atk_i].stale_hitbox(context.rules, hitbox, action);
+                    self.hit_shield(context, &hitbox, power_shield, x);
+                }
+                &CollisionResult::ReflectDef (ref hitbox) => {
+                    self.hitlag = Hitlag::Some (context.rules.knockback.hitlag(hitbox.damage) as u64);
+                }
+                &CollisionResult::AbsorbDef (ref hitbox) => {
+                    self.damage = (self.damage - hitbox.damage).max(0.0);
+                }
+                &CollisionResult::GrabAtk (player_def_i) => {
+                    self.x_vel = 0.0;
+                    self.set_action(context, Action::GrabbingIdle);
+                    self.grabbing = Some(player_def_i);
+                }
+                &CollisionResult::GrabDef (player_atk_i) => {
+                    self.face_right = self.bps_xy(context).0 < context.players[player_atk_i].bps_xy(context).0;
+                    self.location = Location::GrabbedByPlayer (player_atk_i);
+                    self.x_vel = 0.0;
+                    self.y_vel = 0.0;
+                    self.kb_x_vel = 0.0;
+                    self.kb_y_vel = 0.0;
+                    self.hitstun = 0.0;
+                    self.set_hit_by(Some(player_atk_i));
+                    self.grab_timer = (context.rules.grab_mash_base + self.damage * context.rules.grab_mash_damage_mult) as u64;
+                    self.set_action(context, Action::GrabbedIdle);
+                }
+                _ => { }
+            }
+        }
+    }
 
-                    if self.parry_timer == 0 {
-                        self.shield_hp -= hitbox.shield_damage;
-                        if self.shield_hp <= 0.0 {
-                            continue;
-                        }
-                    }
+    /// Apply damage, knockback and hitstun from a hitbox
+    /// atk_x and atk_face_right describe the attacker, used to handle reverse hits
+    /// player_atk_i is None when hit by a stage hazard, the last player to hit keeps the credit
+    fn launch(&mut self, context: &mut StepContext, hitbox: &HitBox, hurtbox: &HurtBox, player_atk_i: Option<usize>, atk_x: f32, atk_face_right: bool) {
+        let damage_done = hitbox.damage * hurtbox.damage_mult * self.handicap.damage_taken_mult;
+        self.damage += damage_done;
+
+        let kbg = hitbox.kbg + hurtbox.kbg_add;
+        let bkb = hitbox.bkb + hurtbox.bkb_add;
+        let kb_damage = match &context.rules.goal {
+            &Goal::Stamina { knockback_damage, .. } => knockback_damage,
+            _                                       => self.damage
+        };
+        let mut kb_vel = context.rules.knockback.knockback(hitbox.damage, damage_done, kb_damage, context.fighter.weight, bkb, kbg);
+        if let Some(player_atk_i) = player_atk_i {
+            kb_vel *= context.players[player_atk_i].handicap.knockback_dealt_mult;
+        }
+
+        // crouch cancel
+        if let (Some(Action::Crouch), true, Some(kb_mult)) = (Action::from_u64(self.action), context.rules.crouch_cancel, context.fighter.crouch_cancel_kb_mult) {
+            kb_vel *= kb_mult;
+        }
+
+        // weak hits such as pummels only deal damage to a held player
+        if self.is_grabbed() && kb_vel <= 50.0 {
+            self.hitlag = Hitlag::Some (context.rules.knockback.hitlag(hitbox.damage) as u64);
+            self.set_hit_by(player_atk_i);
+            return;
+        }
+
+        self.hitstun = match hitbox.hitstun {
+            HitStun::FramesTimesKnockback (frames) => { frames * kb_vel * context.rules.knockback.hitstun_mult }
+            HitStun::Frames               (frames) => { frames as f32 }
+        };
+
+        self.set_airbourne(context);
+
+        if kb_vel > 80.0 {
+            self.set_action(context, Action::DamageFly);
+        }
+        else {
+            self.set_action(context, Action::Damage);
+        }
+
+        // handle sakurai angle
+        let angle_deg = if hitbox.angle == 361.0 {
+            if kb_vel < 32.1 {
+                0.0
+            }
+            else {
+                44.0
+            }
+        } else if hitbox.angle == 180.0 - 361.0 {
+            if kb_vel < 32.1 {
+                180.0
+            }
+            else {
+                180.0 - 44.0
+            }
+        } else {
+            hitbox.angle
+        };
+
+        // convert from degrees to radians
+        let angle_rad = angle_deg.to_radians() + if angle_deg < 0.0 { PI * 2.0 } else { 0.0 };
+
+        // handle reverse hits
+        let behind_player_atk = self.bps_xy(context).0 < atk_x && atk_face_right ||
+                                self.bps_xy(context).0 > atk_x && !atk_face_right;
+        let angle = if hitbox.enable_reverse_hit && behind_player_atk { PI - angle_rad } else { angle_rad };
+
+        // bend the angle towards the attackers nearest teammate
+        let angle = match (hitbox.team_funnel_angle, &context.rules.teams, player_atk_i) {
+            (Some(funnel_angle), &Teams::On { .. }, Some(player_atk_i)) => self.funnel_angle(context, player_atk_i, angle, funnel_angle.to_radians()),
+            _ => angle
+        };
+
+        // debug data
+        self.hit_angle_pre_di = Some(angle);
+        self.hit_angle_post_di = None;
+        self.sdi_offset = None;
+        self.asdi_offset = None;
+        self.frames_since_hit = 0;
+        self.ledge_grabs = 0;
+
+        let hitlag_mult = if let HitboxEffect::Electric { hitlag_mult } = hitbox.effect { hitlag_mult } else { 1.0 };
+        self.hitlag = Hitlag::Launch { counter: (context.rules.knockback.hitlag(hitbox.damage) * hitlag_mult) as u64, kb_vel, angle, wobble_x: 0.0 };
+        self.set_hit_by(player_atk_i);
+        self.face_right = self.bps_xy(context).0 < atk_x;
+
+        match hitbox.effect {
+            HitboxEffect::Reverse => {
+                self.face_right = !self.face_right;
+            }
+            HitboxEffect::Sleep { frames } => {
+                self.stun_timer = frames;
+                self.set_action(context, Action::Sleep);
+            }
+            HitboxEffect::Freeze { frames } => {
+                self.stun_timer = frames;
+                self.set_action(context, Action::Frozen);
+            }
+            HitboxEffect::Stun { frames } => {
+                self.stun_timer = frames;
+                self.set_action(context, Action::Stun);
+            }
+            HitboxEffect::Fire | HitboxEffect::Electric { .. } | HitboxEffect::None => { }
+        }
+    }
+
+    fn push_stale_move(&mut self, rules: &Rules, action: u64) {
+        if let Some(ref stale_moves) = rules.stale_moves {
+            self.stale_moves.insert(0, action);
+            self.stale_moves.truncate(stale_moves.queue_length as usize);
+        }
+    }
+
+    /// Returns the damage multiplier of the current action from how often it is in the stale move queue
+    pub fn stale_mult(&self, rules: &Rules) -> f32 {
+        self.action_stale_mult(rules, self.action)
+    }
+
+    /// Returns the damage multiplier of the passed action from how often it is in the stale move queue
+    fn action_stale_mult(&self, rules: &Rules, action: u64) -> f32 {
+        if let Some(ref stale_moves) = rules.stale_moves {
+            let reduction: f32 = self.stale_moves.iter()
+                .zip(stale_moves.scaling.iter())
+                .filter(|&(stale_action, _)| *stale_action == action)
+                .map(|(_, scaling)| scaling)
+                .sum();
+            (1.0 - reduction).max(0.0)
+        } else {
+            1.0
+        }
+    }
+
+    /// Returns the hitbox with its damage, shield damage and knockback reduced by staling
+    /// action is the action the hitbox belongs to, for projectiles this is the action they were fired from
+    fn stale_hitbox(&self, rules: &Rules, hitbox: &HitBox, action: u64) -> HitBox {
+        let stale_mult = self.action_stale_mult(rules, action);
+        HitBox {
+            damage:        hitbox.damage * stale_mult,
+            shield_damage: hitbox.shield_damage * stale_mult,
+            bkb:           hitbox.bkb * stale_mult,
+            kbg:           hitbox.kbg * stale_mult,
+            .. hitbox.clone()
+        }
+    }
 
-                    let analog_mult = 1.0 - (self.shield_analog - 0.3) / 0.7;
-                    let vel_mult = if self.parry_timer > 0 { 1.0 } else { 0.6 };
-                    let x_diff = self.bps_xy(context).0 - context.players[player_atk_i].bps_xy(context).0;
-                    let vel = (hitbox.damage.floor() * (0.195 * analog_mult + 0.09) + 0.4) * vel_mult;
-                    self.x_vel = vel.min(2.0) * x_diff.signum();
-                    self.shield_stun_timer = (hitbox.damage.floor() * (analog_mult + 0.3) * 0.975 + 2.0) as u64;
-                    self.hitlag = Hitlag::Some ((hitbox.damage / 3.0 + 3.0) as u64);
+    /// Hit the player with the first active stage hazard they are within
+    pub fn step_hazards(&mut self, context: &mut StepContext, game_frame: usize) {
+        if self.hazard_timer > 0 {
+            self.hazard_timer -= 1;
+        }
+        if !context.rules.hazards || self.hazard_timer > 0 || self.is_intangible() || self.is_eliminated() {
+            return;
+        }
+        if let Hitlag::None = self.hitlag { } else {
+            return;
+        }
+
+        let (x, y) = self.bps_xy(context);
+        for hazard in context.stage.hazards.iter() {
+            if let Some(ref hitbox) = hazard.hitbox {
+                if hazard.active(game_frame) && hazard.contains_point(context.surfaces, x, y) {
+                    // the hazard is treated as an attacker facing away from its centre so reverse hits never occur
+                    let hazard_x = hazard.bounds(context.surfaces).map(|rect| (rect.left() + rect.right()) / 2.0).unwrap_or(x);
+                    self.hit_particles((x, y), hitbox);
+                    self.launch(context, hitbox, &HurtBox::default(), None, hazard_x, x >= hazard_x);
+                    self.hazard_timer = hazard.rehit_frames;
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Returns the velocity added by the wind of all active stage hazards the player is within
+    fn hazard_wind(&self, context: &StepContext, game_frame: usize) -> (f32, f32) {
+        if !context.rules.hazards {
+            return (0.0, 0.0);
+        }
+
+        let (x, y) = self.bps_xy(context);
+        context.stage.hazards.iter()
+            .filter(|hazard| hazard.active(game_frame) && hazard.contains_point(context.surfaces, x, y))
+            .fold((0.0, 0.0), |(wind_x, wind_y), hazard| (wind_x + hazard.wind_x, wind_y + hazard.wind_y))
+    }
+
+    /// Returns the angle rotated up to max_funnel radians towards the nearest teammate of the attacker
+    fn funnel_angle(&self, context: &StepContext, player_atk_i: usize, angle: f32, max_funnel: f32) -> f32 {
+        let (x, y) = self.bps_xy(context);
+        let team = context.players[player_atk_i].team;
+        if team == self.team {
+            return angle;
+        }
+
+        let mut nearest: Option<(f32, f32)> = None;
+        for (i, player) in context.players.iter().enumerate() {
+            if i != player_atk_i && player.team == team && !player.is_eliminated() {
+                let (team_x, team_y) = player.bps_xy(context);
+                let diff = (team_x - x, team_y - y);
+                if nearest.map_or(true, |n| diff.0 * diff.0 + diff.1 * diff.1 < n.0 * n.0 + n.1 * n.1) {
+                    nearest = Some(diff);
                 }
-                _ => { }
             }
         }
+
+        if let Some((diff_x, diff_y)) = nearest {
+            let mut funnel = diff_y.atan2(diff_x) - angle;
+            // take the shortest way around the circle
+            while funnel > PI {
+                funnel -= PI * 2.0;
+            }
+            while funnel < -PI {
+                funnel += PI * 2.0;
+            }
+            (angle + funnel.max(-max_funnel).min(max_funnel)).rem_euclid(PI * 2.0)
+        } else {
+            angle
+        }
+    }
+
+    fn hit_shield(&mut self, context: &StepContext, hitbox: &HitBox, power_shield: &Option<PowerShield>, atk_x: f32) {
+        if let &Some(ref power_shield) = power_shield {
+            if let (Some(Action::PowerShield), &Some(ref parry)) = (Action::from_u64(self.action), &power_shield.parry) {
+                if parry.window > self.frame as u64 {
+                    self.parry_timer = parry.duration;
+                }
+            }
+        }
+
+        if self.parry_timer == 0 {
+            self.shield_hp -= hitbox.shield_damage;
+            if self.shield_hp <= 0.0 {
+                return;
+            }
+        }
+
+        let analog_mult = 1.0 - (self.shield_analog - 0.3) / 0.7;
+        let vel_mult = if self.parry_timer > 0 { 1.0 } else { 0.6 };
+        let x_diff = self.bps_xy(context).0 - atk_x;
+        let vel = (hitbox.damage.floor() * (0.195 * analog_mult + 0.09) + 0.4) * vel_mult;
+        self.x_vel = vel.min(2.0) * x_diff.signum();
+        self.shield_stun_timer = (hitbox.damage.floor() * (analog_mult + 0.3) * 0.975 + 2.0) as u64;
+        self.hitlag = Hitlag::Some (context.rules.knockback.hitlag(hitbox.damage) as u64);
     }
 
     /*
@@ -596,14 +944,18 @@ impl Player {
         }
         // The code from this point onwards can assume we are on a valid action and frame
 
+        self.projectile_spawns.clear();
+
         match self.hitlag.clone() {
             Hitlag::Some (_) => {
                 self.hitlag.decrement();
             }
             Hitlag::Launch { kb_vel, angle, .. } => {
                 self.hitlag.wobble(&mut context.rng);
+                self.smash_di(context);
 
                 if self.hitlag.decrement() {
+                    self.automatic_smash_di(context);
                     self.hitlag_def_end(context, kb_vel, angle);
                 }
             }
@@ -628,6 +980,73 @@ impl Player {
         angle - offset
     }
 
+    /// Flicking the stick during hitlag nudges the player in the direction of the flick
+    fn smash_di(&mut self, context: &mut StepContext) {
+        if let Some(distance) = context.rules.sdi_distance {
+            let x = context.input[0].stick_x;
+            let y = context.input[0].stick_y;
+            let prev_x = context.input[1].stick_x;
+            let prev_y = context.input[1].stick_y;
+            let magnitude = (x * x + y * y).sqrt();
+            let prev_magnitude = (prev_x * prev_x + prev_y * prev_y).sqrt();
+
+            if magnitude > 0.7 && prev_magnitude < 0.3 {
+                let d_x = x / magnitude * distance;
+                let d_y = y / magnitude * distance;
+                self.smash_di_move(context, d_x, d_y);
+
+                let (total_x, total_y) = self.sdi_offset.unwrap_or((0.0, 0.0));
+                self.sdi_offset = Some((total_x + d_x, total_y + d_y));
+            }
+        }
+    }
+
+    /// Holding a stick on the final frame of hitlag nudges the player in the held direction, the c-stick takes priority
+    fn automatic_smash_di(&mut self, context: &mut StepContext) {
+        if let Some(distance) = context.rules.asdi_distance {
+            let c_stick = (context.input[0].c_stick_x, context.input[0].c_stick_y);
+            let stick = (context.input[0].stick_x, context.input[0].stick_y);
+            let c_stick_magnitude = (c_stick.0 * c_stick.0 + c_stick.1 * c_stick.1).sqrt();
+            let stick_magnitude = (stick.0 * stick.0 + stick.1 * stick.1).sqrt();
+
+            let direction = if c_stick_magnitude > 0.7 {
+                Some((c_stick.0 / c_stick_magnitude, c_stick.1 / c_stick_magnitude))
+            } else if stick_magnitude > 0.7 {
+                Some((stick.0 / stick_magnitude, stick.1 / stick_magnitude))
+            } else {
+                None
+            };
+
+            if let Some((x, y)) = direction {
+                let d_x = x * distance;
+                let d_y = y * distance;
+                self.smash_di_move(context, d_x, d_y);
+                self.asdi_offset = Some((d_x, d_y));
+            }
+        }
+    }
+
+    /// Grounded players only move along the floor, airbourne players cannot be moved through the stage
+    fn smash_di_move(&mut self, context: &mut StepContext, d_x: f32, d_y: f32) {
+        match self.location.clone() {
+            Location::Airbourne { x, y } => {
+                let new_p = (x + d_x, y + d_y);
+                if !self.wall_stage_collision(context, (x, y), new_p) &&
+                    !self.ceiling_stage_collision(context, (x, y), new_p) &&
+                    self.land_stage_collision(context, (x, y), new_p).is_none()
+                {
+                    self.location = Location::Airbourne { x: new_p.0, y: new_p.1 };
+                }
+            }
+            Location::Surface { platform_i, x } => {
+                if let Some(platform) = context.surfaces.get(platform_i) {
+                    self.location = Location::Surface { platform_i, x: platform.plat_x_clamp(x + d_x) };
+                }
+            }
+            _ => { }
+        }
+    }
+
     fn hitlag_def_end(&mut self, context: &mut StepContext, kb_vel: f32, angle: f32) {
         let angle = if (kb_vel >= 80.0 || self.is_airbourne() || (angle != 0.0 && angle != PI)) // can di
             && !(context.input[0].stick_x == 0.0 && context.input[0].stick_y == 0.0) // not deadzone
@@ -642,10 +1061,11 @@ impl Player {
         let (sin, cos) = angle.sin_cos();
         self.x_vel = 0.0;
         self.y_vel = 0.0;
-        self.kb_x_vel = cos * kb_vel * 0.03;
-        self.kb_y_vel = sin * kb_vel * 0.03;
-        self.kb_x_dec = cos * 0.051;
-        self.kb_y_dec = sin * 0.051;
+        let formula = &context.rules.knockback;
+        self.kb_x_vel = cos * kb_vel * formula.launch_vel_mult;
+        self.kb_y_vel = sin * kb_vel * formula.launch_vel_mult;
+        self.kb_x_dec = cos * formula.launch_dec;
+        self.kb_y_dec = sin * formula.launch_dec;
 
         if self.kb_y_vel == 0.0 {
             if kb_vel >= 80.0 {
@@ -682,6 +1102,10 @@ impl Player {
             self.parry_timer -= 1;
         }
 
+        if self.intangible_timer > 0 {
+            self.intangible_timer -= 1;
+        }
+
         if self.shield_stun_timer > 0 {
             self.shield_stun_timer -= 1;
         }
@@ -697,10 +1121,13 @@ impl Player {
             }
         }
 
+        self.hit_by_timer += 1;
         self.frames_since_hit += 1;
         if self.frames_since_hit > 60 {
             self.hit_angle_pre_di = None;
             self.hit_angle_post_di = None;
+            self.sdi_offset = None;
+            self.asdi_offset = None;
         }
 
         self.tech_timer = match (self.tech_timer.clone(), context.fighter.tech.clone()) {
@@ -759,6 +1186,8 @@ impl Player {
             self.hitlist.clear();
         }
 
+        self.projectile_spawns.extend(fighter_frame.projectile_spawns.iter().cloned());
+
         self.set_action_called = false;
         self.new_action = false;
         self.frame_step(context);
@@ -791,7 +1220,8 @@ impl Player {
                 Action::Fall
                 => self.aerial_action(context),
 
-                Action::JumpF      | Action::JumpB
+                Action::JumpF      | Action::JumpB |
+                Action::WallJump
                 => self.jump_action(context),
 
                 Action::Jab       | Action::Jab2 |
@@ -815,7 +1245,7 @@ impl Player {
                 Action::DamageFall       => self.damage_fall_action(context),
                 Action::Damage           => self.damage_action(context),
                 Action::MissedTechIdle   => self.missed_tech_action(context),
-                Action::MissedTechStart  => self.missed_tech_start_action(context.fighter),
+                Action::MissedTechStart  => self.missed_tech_start_action(context),
                 Action::AerialDodge      => self.aerialdodge_action(context),
                 Action::SpecialFall      => self.specialfall_action(context),
                 Action::Dtilt            => self.dtilt_action(context),
@@ -836,6 +1266,22 @@ impl Player {
                 Action::ShieldBreakFall  => self.shield_break_fall_action(context.fighter),
                 Action::ShieldBreakGetup => self.shield_break_getup_action(),
                 Action::Stun             => self.stun_action(context),
+                Action::Sleep |
+                Action::Frozen           => self.stunned_action(context),
+                Action::NspecialGround | Action::NspecialAir |
+                Action::SspecialGround | Action::SspecialAir |
+                Action::UspecialGround | Action::UspecialAir |
+                Action::DspecialGround | Action::DspecialAir
+                => self.special_action(context),
+
+                Action::GrabbingIdle     => self.grabbing_idle_action(context),
+                Action::Pummel           => self.pummel_action(context),
+                Action::GrabbedIdle      => self.grabbed_idle_action(context),
+                Action::GrabbedEnd       => self.grabbed_end_action(context),
+                Action::GrabbingEnd |
+                Action::ThrowF | Action::ThrowB |
+                Action::ThrowU | Action::ThrowD
+                => self.apply_friction(context),
                 _ => { }
             }
         }
@@ -899,9 +1345,9 @@ impl Player {
         self.ledge_idle_timer += 1;
     }
 
-    fn missed_tech_start_action(&mut self, fighter: &Fighter) {
+    fn missed_tech_start_action(&mut self, context: &StepContext) {
         if self.frame == -1 {
-            self.apply_friction(fighter);
+            self.apply_friction(context);
         } else {
             self.x_vel = 0.0;
         }
@@ -931,7 +1377,7 @@ impl Player {
             }
 
             self.hitstun -= 1.0;
-            self.apply_friction(context.fighter);
+            self.apply_friction(context);
         }
     }
 
@@ -950,7 +1396,7 @@ impl Player {
                 self.fall_action(context.fighter);
             }
             else {
-                self.apply_friction(context.fighter);
+                self.apply_friction(context);
             }
         }
     }
@@ -966,9 +1412,7 @@ impl Player {
     fn damage_fall_action(&mut self, context: &mut StepContext) {
         if self.interruptible(context.fighter) {
             if self.check_attacks_aerial(context) { }
-            else if context.input.b.press {
-                // special attack
-            }
+            else if self.check_special(context) { }
             else if self.check_jump_aerial(context) { }
             else if
                 (context.input[0].stick_x >  0.7 && context.input[1].stick_x <  0.7) ||
@@ -991,9 +1435,7 @@ impl Player {
 
     fn spawn_idle(&mut self, context: &mut StepContext) {
         if self.check_attacks_aerial(context) { }
-        else if context.input.b.press {
-            // special attack
-        }
+        else if self.check_special(context) { }
         else if self.check_jump_aerial(context) { }
         else if context.input.l.press || context.input.r.press {
             self.aerialdodge(context);
@@ -1009,9 +1451,8 @@ impl Player {
     fn aerial_action(&mut self, context: &mut StepContext) {
         if self.interruptible(context.fighter) {
             if self.check_attacks_aerial(context) { }
-            else if context.input.b.press {
-                // special attack
-            }
+            else if self.check_special(context) { }
+            else if self.check_wall_jump(context) { }
             else if self.check_jump_aerial(context) { }
             else if context.input.l.press || context.input.r.press {
                 self.aerialdodge(context);
@@ -1029,9 +1470,7 @@ impl Player {
 
     fn jump_action(&mut self, context: &mut StepContext) {
         if self.check_attacks_aerial(context) { }
-        else if context.input.b.press {
-            // special attack
-        }
+        else if self.check_special(context) { }
         else if self.check_jump_aerial(context) { }
         else if context.input.l.press || context.input.r.press {
             self.aerialdodge(context);
@@ -1086,13 +1525,13 @@ impl Player {
         }
         else if self.check_jump(context) { }
         else if self.check_shield(context) { }
-        else if self.check_special(context) { } // TODO: No neutral special
+        else if self.check_special(context) { }
         else if self.check_smash(context) { }
         else if self.check_attacks(context) { }
         else if self.check_grab(context) { }
         else if self.check_taunt(context) { }
         else {
-            self.apply_friction(&context.fighter);
+            self.apply_friction(context);
         }
     }
 
@@ -1102,13 +1541,13 @@ impl Player {
         }
         else if self.check_jump(context) { }
         else if self.check_shield(context) { }
-        else if self.check_special(context) { } // TODO: No neutral special
+        else if self.check_special(context) { }
         else if self.check_smash(context) { }
         else if self.check_attacks(context) { }
         else if self.check_grab(context) { }
         else if self.check_taunt(context) { }
         else {
-            self.apply_friction(&context.fighter);
+            self.apply_friction(context);
         }
     }
 
@@ -1122,7 +1561,7 @@ impl Player {
 
         if self.check_jump(context) { }
         else {
-            self.apply_friction(&context.fighter);
+            self.apply_friction(context);
         }
     }
 
@@ -1130,18 +1569,18 @@ impl Player {
         if self.interruptible(&context.fighter) {
             if self.check_pass_platform(context) { }
             else if self.check_shield(context) { }
-            else if self.check_special(context) { } // TODO: no neutral/side special
+            else if self.check_special(context) { }
             else if self.check_smash(context) { }
             else if self.check_attacks(context) { }
             else if self.check_grab(context) { }
             else if self.check_taunt(context) { }
             else if self.check_jump(context) { }
             else {
-                self.apply_friction(&context.fighter);
+                self.apply_friction(context);
             }
         }
         else {
-            self.apply_friction(&context.fighter);
+            self.apply_friction(context);
         }
     }
 
@@ -1150,7 +1589,7 @@ impl Player {
             if context.input.stick_y.value > -0.61 { self.set_action(context, Action::CrouchEnd); }
             if self.check_jump(context) { }
             else if self.check_shield(context) { }
-            else if self.check_special(context) { } // TODO: no neutral/side special
+            else if self.check_special(context) { }
             else if self.check_smash(context) { }
             else if self.check_attacks(context) { }
             else if self.check_grab(context) { }
@@ -1159,11 +1598,11 @@ impl Player {
             else if self.check_smash_turn(context) { }
             else if self.check_tilt_turn(context) { }
             else {
-                self.apply_friction(&context.fighter);
+                self.apply_friction(context);
             }
         }
         else {
-            self.apply_friction(&context.fighter);
+            self.apply_friction(context);
         }
     }
 
@@ -1171,7 +1610,7 @@ impl Player {
         if self.interruptible(&context.fighter) {
             if self.check_jump(context) { }
             else if self.check_shield(context) { }
-            else if self.check_special(context) { } // TODO: no neutral/side special
+            else if self.check_special(context) { }
             else if self.check_smash(context) { }
             else if self.check_attacks(context) { }
             else if self.check_grab(context) { }
@@ -1181,11 +1620,11 @@ impl Player {
             else if self.check_walk(context) { }
             else if self.check_taunt(context) { }
             else {
-                self.apply_friction(&context.fighter);
+                self.apply_friction(context);
             }
         }
         else {
-            self.apply_friction(&context.fighter);
+            self.apply_friction(context);
         }
     }
 
@@ -1204,11 +1643,11 @@ impl Player {
             else if self.check_tilt_turn(context) { }
             else if self.check_walk(context) { }
             else {
-                self.apply_friction(&context.fighter);
+                self.apply_friction(context);
             }
         }
         else {
-            self.apply_friction(&context.fighter);
+            self.apply_friction(context);
         }
     }
 
@@ -1233,11 +1672,11 @@ impl Player {
                 self.set_action(context, Action::Crouch);
             }
             else {
-                self.apply_friction(&context.fighter);
+                self.apply_friction(context);
             }
         }
         else {
-            self.apply_friction(&context.fighter);
+            self.apply_friction(context);
         }
     }
 
@@ -1260,11 +1699,11 @@ impl Player {
                 self.set_action(context, Action::Crouch);
             }
             else {
-                self.apply_friction(&context.fighter);
+                self.apply_friction(context);
             }
         }
         else {
-            self.apply_friction(&context.fighter);
+            self.apply_friction(context);
         }
     }
 
@@ -1304,7 +1743,7 @@ impl Player {
             let vel_max = context.fighter.walk_max_vel * context.input[0].stick_x;
 
             if self.x_vel.abs() > vel_max.abs() {
-                self.apply_friction(&context.fighter);
+                self.apply_friction(context);
             }
             else {
                 let acc = (vel_max - self.x_vel) * (2.0/context.fighter.walk_max_vel) * (context.fighter.walk_init_vel + context.fighter.walk_acc);
@@ -1327,7 +1766,7 @@ impl Player {
 
         if self.frame > 0 {
             if context.input[0].stick_x.abs() < 0.3 {
-                self.apply_friction(&context.fighter);
+                self.apply_friction(context);
             }
             else {
                 let vel_max = context.input[0].stick_x * context.fighter.dash_run_term_vel;
@@ -1335,7 +1774,7 @@ impl Player {
 
                 self.x_vel += acc;
                 if (vel_max > 0.0 && self.x_vel > vel_max) || (vel_max < 0.0 && self.x_vel < vel_max) {
-                    self.apply_friction(&context.fighter);
+                    self.apply_friction(context);
                     if (vel_max > 0.0 && self.x_vel < vel_max) || (vel_max < 0.0 && self.x_vel > vel_max) {
                         self.x_vel = vel_max;
                     }
@@ -1407,7 +1846,7 @@ impl Player {
             self.set_action(context, Action::RunTurn);
         }
         else {
-            self.apply_friction(context.fighter);
+            self.apply_friction(context);
         }
     }
 
@@ -1450,11 +1889,11 @@ impl Player {
             self.action = Action::PowerShield as u64;
             self.frame = if power_shield_len >= 2 { 1 } else { 0 }; // change self.frame so that a powershield isnt laggier than a normal shield
 
-            self.apply_friction(context.fighter);
+            self.apply_friction(context);
             self.shield_shared_action(context);
         }
         else {
-            self.apply_friction(context.fighter);
+            self.apply_friction(context);
             self.shield_shared_action(context);
         }
     }
@@ -1473,11 +1912,11 @@ impl Player {
                 self.set_action(context, Action::ShieldOff);
             }
 
-            self.apply_friction(context.fighter);
+            self.apply_friction(context);
             self.shield_shared_action(context);
         }
         else {
-            self.apply_friction(context.fighter);
+            self.apply_friction(context);
             self.shield_shared_action(context);
         }
     }
@@ -1490,7 +1929,7 @@ impl Player {
         if !lock && self.check_jump(context) { }
         else if !lock && self.check_pass_platform(context) { }
         else {
-            self.apply_friction(context.fighter);
+            self.apply_friction(context);
             self.shield_shared_action(context);
         }
     }
@@ -1507,13 +1946,13 @@ impl Player {
                 self.shield_shared_action(context);
             }
             _ => {
-                self.apply_friction(context.fighter);
+                self.apply_friction(context);
             }
         }
     }
 
     fn shield_shared_action(&mut self, context: &mut StepContext) {
-        self.apply_friction(context.fighter);
+        self.apply_friction(context);
         if let Some(ref shield) = context.fighter.shield {
             // shield analog
             self.shield_analog = if context.input[0].l || context.input[0].r {
@@ -1538,7 +1977,7 @@ impl Player {
                 self.set_action(context, Action::ShieldBreakFall);
                 self.shield_hp = 0.0;
                 self.kb_y_vel = shield.break_vel;
-                self.kb_y_dec = 0.051;
+                self.kb_y_dec = context.rules.knockback.launch_dec;
                 self.kb_x_dec = 0.0;
                 self.set_airbourne(context);
             }
@@ -1554,17 +1993,134 @@ impl Player {
     }
 
     fn stun_action(&mut self, context: &mut StepContext) {
-        self.apply_friction(context.fighter);
         if self.shield_hp > 30.0 {
             self.shield_hp = 30.0;
         }
+        self.stunned_action(context);
+    }
 
-        self.stun_timer -= 1;
+    /// Used by stun, sleep and frozen
+    fn stunned_action(&mut self, context: &mut StepContext) {
+        if self.is_airbourne() {
+            self.fall_action(context.fighter);
+        } else {
+            self.apply_friction(context);
+        }
 
-        // TODO: Mashout
+        self.stun_timer = self.stun_timer.saturating_sub(Player::mash(&context.input));
 
-        if self.stun_timer <= 0 {
-            self.set_action(context, Action::Idle);
+        if self.stun_timer == 0 {
+            if self.is_airbourne() {
+                self.set_action(context, Action::Fall);
+            } else {
+                self.set_action(context, Action::Idle);
+            }
+        }
+    }
+
+    /// Frames to take off a mashable timer, button presses and stick flicks speed it up
+    fn mash(input: &PlayerInput) -> u64 {
+        let mut mash = 1;
+        if input.a.press || input.b.press || input.x.press || input.y.press {
+            mash += 3;
+        }
+        if input.stick_x.diff.abs() > 0.5 || input.stick_y.diff.abs() > 0.5 {
+            mash += 3;
+        }
+        mash
+    }
+
+    fn grabbing_idle_action(&mut self, context: &mut StepContext) {
+        self.apply_friction(context);
+        if self.check_grab_released(context) {
+            return;
+        }
+
+        let stick_x = self.relative_f(context.input.stick_x.value);
+        let stick_y = context.input.stick_y.value;
+        let c_stick_x = self.relative_f(context.input.c_stick_x.value);
+        let c_stick_y = context.input.c_stick_y.value;
+
+        if context.input.a.press || context.input.z.press {
+            self.set_action(context, Action::Pummel);
+        }
+        else if stick_x > 0.3 || c_stick_x > 0.3 {
+            self.set_action(context, Action::ThrowF);
+        }
+        else if stick_x < -0.3 || c_stick_x < -0.3 {
+            self.set_action(context, Action::ThrowB);
+        }
+        else if stick_y > 0.3 || c_stick_y > 0.3 {
+            self.set_action(context, Action::ThrowU);
+        }
+        else if stick_y < -0.3 || c_stick_y < -0.3 {
+            self.set_action(context, Action::ThrowD);
+        }
+    }
+
+    fn pummel_action(&mut self, context: &mut StepContext) {
+        self.apply_friction(context);
+        self.check_grab_released(context);
+    }
+
+    /// The held player may have escaped or been knocked out of the grab
+    fn check_grab_released(&mut self, context: &mut StepContext) -> bool {
+        let released = match self.grabbing {
+            Some(player_def_i) => {
+                match context.players.get(player_def_i).map(|x| &x.location) {
+                    Some(&Location::GrabbedByPlayer (_)) => false,
+                    _ => true
+                }
+            }
+            None => true
+        };
+        if released {
+            self.set_action(context, Action::GrabbingEnd);
+        }
+        released
+    }
+
+    fn grabbed_idle_action(&mut self, context: &mut StepContext) {
+        let player_atk_i = if let Location::GrabbedByPlayer (player_atk_i) = self.location { player_atk_i } else { return };
+        let (grabber_holding, grabber_action) = {
+            let player_atk = &context.players[player_atk_i];
+            (player_atk.grabbing.is_some(), Action::from_u64(player_atk.action))
+        };
+        if !grabber_holding {
+            self.grab_release(context, player_atk_i);
+            return;
+        }
+
+        // Can only escape while held, once a throw has started it is too late
+        if let Some(Action::GrabbingIdle) | Some(Action::Pummel) = grabber_action {
+            self.grab_timer = self.grab_timer.saturating_sub(Player::mash(&context.input));
+
+            if self.grab_timer == 0 {
+                self.grab_release(context, player_atk_i);
+            }
+        }
+    }
+
+    /// Place the held player beside the grabber, this also releases the grabber as they will no longer see a held player
+    fn grab_release(&mut self, context: &mut StepContext, player_atk_i: usize) {
+        let (x, y) = self.bps_xy(context);
+        if let Location::Surface { platform_i, .. } = context.players[player_atk_i].location {
+            let platform = &context.surfaces[platform_i];
+            self.location = Location::Surface { platform_i, x: platform.world_x_to_plat_x_clamp(x) };
+        } else {
+            self.location = Location::Airbourne { x, y };
+            self.fastfalled = false;
+        }
+        self.x_vel = self.relative_f(-1.0);
+        self.set_action(context, Action::GrabbedEnd);
+    }
+
+    fn grabbed_end_action(&mut self, context: &mut StepContext) {
+        if self.is_platform() {
+            self.apply_friction(context);
+        }
+        else {
+            self.air_drift(context);
         }
     }
 
@@ -1776,9 +2332,24 @@ impl Player {
         }
     }
 
-    fn check_special(&mut self, context: &StepContext) -> bool {
+    fn check_special(&mut self, context: &mut StepContext) -> bool {
         if context.input.b.press {
-            // special attack
+            let stick_x = context.input[0].stick_x;
+            let stick_y = context.input[0].stick_y;
+            let air = self.is_airbourne();
+            if stick_y > 0.5 {
+                self.set_action(context, if air { Action::UspecialAir } else { Action::UspecialGround });
+            }
+            else if stick_y < -0.5 {
+                self.set_action(context, if air { Action::DspecialAir } else { Action::DspecialGround });
+            }
+            else if stick_x.abs() > 0.5 {
+                self.face_right = stick_x > 0.0;
+                self.set_action(context, if air { Action::SspecialAir } else { Action::SspecialGround });
+            }
+            else {
+                self.set_action(context, if air { Action::NspecialAir } else { Action::NspecialGround });
+            }
             true
         }
         else {
@@ -1913,6 +2484,7 @@ impl Player {
             Some(Action::JumpB)          => self.set_action(context, Action::Fall),
             Some(Action::JumpAerialF)    => self.set_action(context, Action::AerialFall),
             Some(Action::JumpAerialB)    => self.set_action(context, Action::AerialFall),
+            Some(Action::WallJump)       => self.set_action(context, Action::Fall),
             Some(Action::SmashTurn)      => self.set_action(context, Action::Idle),
             Some(Action::RunTurn)        => {
                 if self.relative_f(context.input[0].stick_x) > 0.6 {
@@ -1986,6 +2558,8 @@ impl Player {
             Some(Action::TechF)            => self.set_action(context, Action::Idle),
             Some(Action::TechN)            => self.set_action(context, Action::Idle),
             Some(Action::TechB)            => self.set_action(context, Action::Idle),
+            Some(Action::WallTech)         => self.set_action(context, Action::Fall),
+            Some(Action::CeilingTech)      => self.set_action(context, Action::Fall),
             Some(Action::MissedTechGetupF) => self.set_action(context, Action::Idle),
             Some(Action::MissedTechGetupN) => self.set_action(context, Action::Idle),
             Some(Action::MissedTechGetupB) => self.set_action(context, Action::Idle),
@@ -1997,6 +2571,8 @@ impl Player {
             Some(Action::MissedTechStart)  => self.set_action(context, Action::MissedTechIdle),
             Some(Action::ShieldBreakFall)  => self.set_action(context, Action::ShieldBreakFall),
             Some(Action::Stun)             => self.set_action(context, Action::Stun),
+            Some(Action::Sleep)            => self.set_action(context, Action::Sleep),
+            Some(Action::Frozen)           => self.set_action(context, Action::Frozen),
             Some(Action::ShieldBreakGetup) => {
                 self.stun_timer = 490;
                 self.set_action(context, Action::Stun);
@@ -2031,12 +2607,41 @@ impl Player {
             Some(Action::BairLand) => self.set_action(context, Action::Idle),
             Some(Action::NairLand) => self.set_action(context, Action::Idle),
 
+            // Specials
+            Some(Action::NspecialGround) | Some(Action::NspecialAir) |
+            Some(Action::SspecialGround) | Some(Action::SspecialAir) |
+            Some(Action::UspecialGround) | Some(Action::UspecialAir) |
+            Some(Action::DspecialGround) | Some(Action::DspecialAir) => {
+                if self.is_airbourne() {
+                    self.set_action(context, Action::SpecialFall);
+                } else {
+                    self.set_action(context, Action::Idle);
+                }
+            }
+
             // Taunts
             Some(Action::TauntUp)    => self.set_action(context, Action::Idle),
             Some(Action::TauntDown)  => self.set_action(context, Action::Idle),
             Some(Action::TauntLeft)  => self.set_action(context, Action::Idle),
             Some(Action::TauntRight) => self.set_action(context, Action::Idle),
 
+            // Grabs
+            Some(Action::GrabbingIdle) => self.set_action(context, Action::GrabbingIdle),
+            Some(Action::GrabbingEnd)  => self.set_action(context, Action::Idle),
+            Some(Action::Pummel)       => self.set_action(context, Action::GrabbingIdle),
+            Some(Action::ThrowF)       => self.set_action(context, Action::Idle),
+            Some(Action::ThrowB)       => self.set_action(context, Action::Idle),
+            Some(Action::ThrowU)       => self.set_action(context, Action::Idle),
+            Some(Action::ThrowD)       => self.set_action(context, Action::Idle),
+            Some(Action::GrabbedIdle)  => self.set_action(context, Action::GrabbedIdle),
+            Some(Action::GrabbedEnd)   => {
+                if self.is_platform() {
+                    self.set_action(context, Action::Idle);
+                } else {
+                    self.set_action(context, Action::Fall);
+                }
+            }
+
             Some(Action::Eliminated)         => self.set_action(context, Action::Eliminated),
             Some(Action::DummyFramePreStart) => self.set_action(context, Action::Spawn),
         };
@@ -2124,6 +2729,22 @@ impl Player {
         }
     }
 
+    /// Specials can start on the ground and continue in the air or vice versa, so behaviour depends on location rather than the action
+    fn special_action(&mut self, context: &mut StepContext) {
+        if self.is_airbourne() {
+            if self.interruptible(&context.fighter) {
+                self.aerial_action(context);
+            }
+            else {
+                self.fall_action(context.fighter);
+                self.air_drift(context);
+            }
+        }
+        else {
+            self.ground_idle_action(context);
+        }
+    }
+
     fn specialfall_action(&mut self, context: &mut StepContext) {
         self.fall_action(context.fighter);
         self.air_drift(context);
@@ -2164,7 +2785,7 @@ impl Player {
                 if self.is_airbourne() {
                     self.kb_x_vel -= self.kb_x_dec;
                 } else {
-                    self.kb_x_vel -= vel_dir * context.fighter.friction;
+                    self.kb_x_vel -= vel_dir * self.friction(context);
                 }
                 if vel_dir != self.kb_x_vel.signum() {
                     self.kb_x_vel = 0.0;
@@ -2195,29 +2816,65 @@ impl Player {
                 VelModify::Add (y_vel) => self.y_vel += y_vel,
                 VelModify::None => { }
             }
+            // an upwards velocity lifts the player off the ground e.g. an up special
+            if self.is_platform() && self.y_vel > 0.0 {
+                self.set_airbourne(context);
+            }
 
-            let x_vel = self.x_vel + self.kb_x_vel + self.relative_f(fighter_frame.x_vel_temp);
-            let y_vel = self.y_vel + self.kb_y_vel + fighter_frame.y_vel_temp;
+            let (wind_x, wind_y) = self.hazard_wind(context, game_frame);
+            let x_vel = self.x_vel + self.kb_x_vel + self.relative_f(fighter_frame.x_vel_temp) + wind_x;
+            let y_vel = self.y_vel + self.kb_y_vel + fighter_frame.y_vel_temp + wind_y;
 
             // update position
             match self.location.clone() {
                 Location::Airbourne { x, y } => {
-                    let new_x = x + x_vel;
-                    let new_y = y + y_vel;
+                    let mut new_x = x + x_vel;
+                    let mut new_y = y + y_vel;
+                    if self.wall_stage_collision(context, (x, y), (new_x, new_y)) {
+                        new_x = x;
+                        self.x_vel = 0.0;
+                        self.kb_x_vel = 0.0;
+                        self.surface_tech(context, Action::WallTech);
+                    }
+                    if self.ceiling_stage_collision(context, (x, y), (new_x, new_y)) {
+                        new_y = y;
+                        self.y_vel = 0.0;
+                        self.kb_y_vel = 0.0;
+                        self.surface_tech(context, Action::CeilingTech);
+                    }
                     if let Some(platform_i) = self.land_stage_collision(context, (x, y), (new_x, new_y)) {
-                        let x = context.stage.surfaces[platform_i].world_x_to_plat_x(new_x);
+                        let x = context.surfaces[platform_i].world_x_to_plat_x(new_x);
                         self.land(context, platform_i, x);
                     } else {
                         self.location = Location::Airbourne { x: new_x, y: new_y };
                     }
                 }
                 Location::Surface { platform_i, mut x } => {
-                    if let Some(platform) = context.stage.surfaces.get(platform_i) {
-                        x += x_vel * platform.floor_angle().unwrap_or_default().cos();
-                        self.floor_move(context, platform, platform_i, x);
+                    match context.surfaces.get(platform_i) {
+                        Some(platform) if platform.floor.is_some() => {
+                            let conveyor = platform.floor.as_ref().map_or(0.0, |floor| floor.conveyor);
+                            x += (x_vel + conveyor) * platform.floor_angle().unwrap_or_default().cos();
+                            self.floor_move(context, platform, platform_i, x);
+                        }
+                        Some(_) => {
+                            // the floor has disappeared from under the player
+                            self.set_airbourne(context);
+                            self.set_action(context, Action::Fall);
+                        }
+                        None => {
+                            self.location = Location::Airbourne { x: 0.0, y: 0.0 };
+                            self.set_action(context, Action::Fall);
+                        }
                     }
-                    else {
-                        self.location = Location::Airbourne { x: 0.0, y: 0.0 };
+                }
+                Location::GrabbedLedge { platform_i, .. } => {
+                    let grabbable = match context.surfaces.get(platform_i) {
+                        Some(platform) => if self.face_right { platform.left_grab() } else { platform.right_grab() },
+                        None           => false
+                    };
+                    if !grabbable {
+                        // the ledge has disappeared from under the players hand
+                        self.set_airbourne(context);
                         self.set_action(context, Action::Fall);
                     }
                 }
@@ -2227,9 +2884,17 @@ impl Player {
             // death
             let blast = &context.stage.blast;
             let (x, y) = self.bps_xy(context);
-            if x < blast.left() || x > blast.right() || y < blast.bot() || y > blast.top() {
+            let out_of_bounds = x < blast.left() || x > blast.right() || y < blast.bot() || y > blast.top();
+            let (hp_depleted, blast_zone_kills) = match &goal {
+                &Goal::Stamina { hp, blast_zone_kills, .. } => (self.damage >= hp && !self.is_eliminated(), blast_zone_kills),
+                _                                           => (false, true)
+            };
+            if hp_depleted || out_of_bounds && blast_zone_kills {
                 self.die(context, player_i, game_frame, goal);
             }
+            else if out_of_bounds {
+                self.return_to_stage(context, player_i);
+            }
 
             // ledge grabs
             let fighter_frame = &context.fighter.actions[self.action as usize].frames[self.frame as usize];
@@ -2243,20 +2908,20 @@ impl Player {
     }
 
     fn floor_move(&mut self, context: &mut StepContext, platform: &Surface, platform_i: usize, x: f32) {
-        let connected_floors = context.stage.connected_floors(platform_i);
+        let connected_floors = connected_floors(context.surfaces, platform_i);
         if platform.plat_x_in_bounds(x) {
             self.location = Location::Surface { platform_i, x };
         }
         else if x < 0.0 && connected_floors.left_i.is_some() {
             let new_platform_i = connected_floors.left_i.unwrap();
-            let new_platform = &context.stage.surfaces[new_platform_i];
+            let new_platform = &context.surfaces[new_platform_i];
             let world_x = platform.plat_x_to_world_x(x);
             let x = new_platform.world_x_to_plat_x(world_x);
             self.floor_move(context, new_platform, new_platform_i, x);
         }
         else if x > 0.0 && connected_floors.right_i.is_some() {
             let new_platform_i = connected_floors.right_i.unwrap();
-            let new_platform = &context.stage.surfaces[new_platform_i];
+            let new_platform = &context.surfaces[new_platform_i];
             let world_x = platform.plat_x_to_world_x(x);
             let x = new_platform.world_x_to_plat_x(world_x);
             self.floor_move(context, new_platform, new_platform_i, x);
@@ -2288,7 +2953,13 @@ impl Player {
         }
     }
 
-    fn apply_friction(&mut self, fighter: &Fighter) {
+    fn apply_friction(&mut self, context: &StepContext) {
+        // on slippery floors every action slides
+        if self.current_floor(context).map_or(false, |floor| floor.slippery) {
+            self.apply_friction_weak(context);
+            return;
+        }
+
         match Action::from_u64(self.action) {
             Some(Action::Idle) |
             Some(Action::Dash) |
@@ -2296,49 +2967,71 @@ impl Player {
             Some(Action::ShieldOn) |
             Some(Action::ShieldOff) |
             Some(Action::Damage)
-              => { self.apply_friction_weak(fighter) }
-            _ => { self.apply_friction_strong(fighter) }
+              => { self.apply_friction_weak(context) }
+            _ => { self.apply_friction_strong(context) }
         }
     }
 
     // TODO: These functions are split up as weak/strong so that one day they may be called individually by player scripts
-    fn apply_friction_weak(&mut self, fighter: &Fighter) {
+    fn apply_friction_weak(&mut self, context: &StepContext) {
+        let friction = self.friction(context);
         if self.x_vel > 0.0 {
-            self.x_vel -= fighter.friction;
+            self.x_vel -= friction;
             if self.x_vel < 0.0 {
                 self.x_vel = 0.0;
             }
         }
         else {
-            self.x_vel += fighter.friction;
+            self.x_vel += friction;
             if self.x_vel > 0.0 {
                 self.x_vel = 0.0;
             }
         }
     }
 
-    fn apply_friction_strong(&mut self, fighter: &Fighter) {
+    fn apply_friction_strong(&mut self, context: &StepContext) {
+        let friction = self.friction(context);
+        // slippery floors dont provide the extra grip needed to stop quickly from a run
+        let mult = if self.current_floor(context).map_or(false, |floor| floor.slippery) { 1.0 } else { 2.0 };
+        let walk_max_vel = context.fighter.walk_max_vel;
         if self.x_vel > 0.0 {
-            self.x_vel -= fighter.friction * if self.x_vel > fighter.walk_max_vel { 2.0 } else { 1.0 };
+            self.x_vel -= friction * if self.x_vel > walk_max_vel { mult } else { 1.0 };
             if self.x_vel < 0.0 {
                 self.x_vel = 0.0;
             }
         }
         else {
-            self.x_vel += fighter.friction * if self.x_vel < -fighter.walk_max_vel { 2.0 } else { 1.0 };
+            self.x_vel += friction * if self.x_vel < -walk_max_vel { mult } else { 1.0 };
             if self.x_vel > 0.0 {
                 self.x_vel = 0.0;
             }
         }
     }
 
+    /// returns the floor the player is standing on
+    fn current_floor<'a>(&self, context: &StepContext<'a>) -> Option<&'a Floor> {
+        if let Location::Surface { platform_i, .. } = self.location {
+            context.surfaces.get(platform_i).and_then(|platform| platform.floor.as_ref())
+        } else {
+            None
+        }
+    }
+
+    /// returns the friction between the fighter and the floor they are standing on
+    fn friction(&self, context: &StepContext) -> f32 {
+        match self.current_floor(context) {
+            Some(floor) => context.fighter.friction * if floor.slippery { 0.25 } else { 1.0 },
+            None        => context.fighter.friction
+        }
+    }
+
     /// returns the index platform that the player will land on
     fn land_stage_collision(&mut self, context: &mut StepContext, old_p: (f32, f32), new_p: (f32, f32)) -> Option<usize> {
         if new_p.1 > old_p.1 {
             return None
         }
 
-        for (surface_i, surface) in context.stage.surfaces.iter().enumerate() {
+        for (surface_i, surface) in context.surfaces.iter().enumerate() {
             if !self.pass_through_platform(context, surface) &&
                 surface.floor.is_some() &&
                 geometry::segments_intersect(old_p, new_p, surface.p1(), surface.p2())
@@ -2349,6 +3042,90 @@ impl Player {
         None
     }
 
+    /// Returns the x offsets from bps of the left and right sides of the ECB
+    fn ecb_sides(&self) -> (f32, f32) {
+        if self.face_right {
+            (self.ecb.left, self.ecb.right)
+        } else {
+            (-self.ecb.right, -self.ecb.left)
+        }
+    }
+
+    fn wall_stage_collision(&self, context: &StepContext, old_p: (f32, f32), new_p: (f32, f32)) -> bool {
+        let (left, right) = self.ecb_sides();
+        let side = if new_p.0 > old_p.0 { right } else if new_p.0 < old_p.0 { left } else { return false };
+        let mid = (self.ecb.top + self.ecb.bottom) / 2.0;
+        let old_side = (old_p.0 + side, old_p.1 + mid);
+        let new_side = (new_p.0 + side, new_p.1 + mid);
+
+        context.surfaces.iter().any(|surface| surface.wall && geometry::segments_intersect(old_side, new_side, surface.p1(), surface.p2()))
+    }
+
+    fn ceiling_stage_collision(&self, context: &StepContext, old_p: (f32, f32), new_p: (f32, f32)) -> bool {
+        if new_p.1 <= old_p.1 {
+            return false;
+        }
+        let old_top = (old_p.0, old_p.1 + self.ecb.top);
+        let new_top = (new_p.0, new_p.1 + self.ecb.top);
+
+        context.surfaces.iter().any(|surface| surface.ceiling && geometry::segments_intersect(old_top, new_top, surface.p1(), surface.p2()))
+    }
+
+    /// A knocked back player that hits a wall or ceiling during the tech window techs off it
+    fn surface_tech(&mut self, context: &mut StepContext, tech_action: Action) {
+        if let Some(Action::DamageFly) | Some(Action::DamageFall) = Action::from_u64(self.action) {
+            if self.tech_timer.is_active() {
+                self.x_vel = 0.0;
+                self.y_vel = 0.0;
+                self.kb_x_vel = 0.0;
+                self.kb_y_vel = 0.0;
+                self.hitstun = 0.0;
+                self.set_action(context, tech_action);
+            }
+        }
+    }
+
+    /// Returns Some(true) if there is a wall within range on the right, Some(false) if there is one on the left
+    fn wall_in_range(&self, context: &StepContext, range: f32) -> Option<bool> {
+        let (x, y) = self.bps_xy(context);
+        let (left, right) = self.ecb_sides();
+        let y = y + (self.ecb.top + self.ecb.bottom) / 2.0;
+        let right_ray = ((x, y), (x + right + range, y));
+        let left_ray  = ((x, y), (x + left  - range, y));
+
+        for surface in context.surfaces.iter().filter(|x| x.wall) {
+            if geometry::segments_intersect(right_ray.0, right_ray.1, surface.p1(), surface.p2()) {
+                return Some(true);
+            }
+            if geometry::segments_intersect(left_ray.0, left_ray.1, surface.p1(), surface.p2()) {
+                return Some(false);
+            }
+        }
+        None
+    }
+
+    /// Flicking the stick away from a nearby wall jumps off it
+    fn check_wall_jump(&mut self, context: &mut StepContext) -> bool {
+        if let Some(ref wall_jump) = context.fighter.wall_jump {
+            let flick_left  = context.input[0].stick_x < -0.7 && context.input[2].stick_x > -0.3;
+            let flick_right = context.input[0].stick_x >  0.7 && context.input[2].stick_x <  0.3;
+            let face_right = match self.wall_in_range(context, wall_jump.range) {
+                Some(true)  if flick_left  => false,
+                Some(false) if flick_right => true,
+                _ => return false
+            };
+
+            self.face_right = face_right;
+            self.x_vel = self.relative_f(wall_jump.x_vel);
+            self.y_vel = wall_jump.y_vel;
+            self.fastfalled = false;
+            self.set_action(context, Action::WallJump);
+            true
+        } else {
+            false
+        }
+    }
+
     pub fn pass_through_platform(&self, context: &mut StepContext, platform: &Surface) -> bool {
         let fighter_frame = &context.fighter.actions[self.action as usize].frames[self.frame as usize];
         platform.is_pass_through() && fighter_frame.pass_through && context.input[0].stick_y <= -0.56
@@ -2403,6 +3180,7 @@ impl Player {
 
     fn land(&mut self, context: &mut StepContext, platform_i: usize, x: f32) {
         let action = Action::from_u64(self.action);
+        let land_cancel = context.fighter.actions[self.action as usize].frames[self.frame as usize].land_cancel;
 
         self.land_frame_skip = match action {
             Some(_) if action.as_ref().map_or(false, |x| x.is_air_attack()) && self.lcancel_timer > 0 => 1,
@@ -2412,8 +3190,10 @@ impl Player {
         };
 
         self.aerial_dodge_frame = if let Some(Action::AerialDodge) = action { Some(self.frame as u64 ) } else { None };
+        self.ledge_grabs = 0;
 
         match action {
+            Some(_) if !land_cancel && action.as_ref().map_or(false, |x| x.is_special()) => { } // the special continues on the ground
             Some(Action::Uair)            => self.set_action(context, Action::UairLand),
             Some(Action::Dair)            => self.set_action(context, Action::DairLand),
             Some(Action::Fair)            => self.set_action(context, Action::FairLand),
@@ -2436,9 +3216,11 @@ impl Player {
                     self.set_action(context, Action::MissedTechStart);
                 }
             }
+            Some(Action::Stun) | Some(Action::Sleep) | Some(Action::Frozen) => { } // remain stunned on the ground
             Some(Action::SpecialFall) |
             Some(Action::AerialDodge) |
             None => self.set_action(context, Action::SpecialLand),
+            Some(_) if action.as_ref().map_or(false, |x| x.is_special()) => self.set_action(context, Action::SpecialLand),
             _ if self.y_vel >= -1.0 => { self.set_action(context, Action::Idle) }, // no impact land
             Some(_) => self.set_action(context, Action::Land)
         }
@@ -2466,16 +3248,50 @@ impl Player {
         self.set_action(context, Action::Dash);
     }
 
-    fn die(&mut self, context: &mut StepContext, player_i: usize, game_frame: usize, goal: Goal) {
-        if context.stage.respawn_points.len() == 0 {
-            self.location = Location::Airbourne { x: 0.0, y: 0.0 };
-            self.face_right = true;
-        } else {
-            let respawn = &context.stage.respawn_points[player_i % context.stage.respawn_points.len()];
+    /// Hits without an attacker such as stage hazards keep the previous attacker and their timer
+    fn set_hit_by(&mut self, player_atk_i: Option<usize>) {
+        if player_atk_i.is_some() {
+            self.hit_by = player_atk_i;
+            self.hit_by_timer = 0;
+        }
+    }
+
+    fn move_to_respawn_point(&mut self, context: &StepContext, player_i: usize) {
+        if let Some(respawn) = context.stage.respawn_point(player_i) {
             self.location = Location::Airbourne { x: respawn.x, y: respawn.y };
             self.face_right = respawn.face_right;
+        } else {
+            self.location = Location::Airbourne { x: 0.0, y: 0.0 };
+            self.face_right = true;
         }
-        self.damage = 0.0;
+    }
+
+    /// Bring an eliminated player back with a stock taken from a teammate
+    pub fn revive(&mut self, context: &mut StepContext, player_i: usize) {
+        self.stocks = Some(1);
+        self.move_to_respawn_point(context, player_i);
+        self.set_action(context, Action::ReSpawn);
+    }
+
+    /// Respawn with a single stock at the start of a sudden death round
+    pub fn start_sudden_death(&mut self, context: &mut StepContext, player_i: usize, damage: f32) {
+        self.return_to_stage(context, player_i);
+        self.stocks = Some(1);
+        self.damage = damage;
+        self.hitlag = Hitlag::None;
+    }
+
+    pub fn eliminate(&mut self, context: &mut StepContext) {
+        self.set_action(context, Action::Eliminated);
+    }
+
+    pub fn is_eliminated(&self) -> bool {
+        self.action == Action::Eliminated as u64
+    }
+
+    fn die(&mut self, context: &mut StepContext, player_i: usize, game_frame: usize, goal: Goal) {
+        self.move_to_respawn_point(context, player_i);
+        self.damage = self.handicap.damage;
         self.x_vel = 0.0;
         self.y_vel = 0.0;
         self.kb_x_vel = 0.0;
@@ -2484,11 +3300,18 @@ impl Player {
         self.fastfalled = false;
         self.hitstun = 0.0;
         self.hitlag = Hitlag::None;
+        self.stale_moves.clear();
 
+        // the attacker only gets the kill if they hit us recently enough
+        let killer = match context.rules.kill_credit_frames {
+            Some(frames) if self.hit_by_timer > frames => None,
+            _                                          => self.hit_by,
+        };
         self.result.deaths.push(DeathRecord {
-            player: self.hit_by,
+            player: killer,
             frame: game_frame,
         });
+        self.hit_by = None;
 
         match goal {
             Goal::LastManStanding => {
@@ -2507,13 +3330,45 @@ impl Player {
             Goal::KillDeathScore => {
                 self.set_action(context, Action::ReSpawn);
             }
+            Goal::Stamina { .. } => {
+                let stocks = self.stocks.map_or(0, |x| x - 1);
+                if self.stocks.is_some() {
+                    self.stocks = Some(stocks);
+                }
+
+                if stocks == 0 {
+                    self.set_action(context, Action::Eliminated);
+                }
+                else {
+                    self.set_action(context, Action::ReSpawn);
+                }
+            }
         }
     }
 
+    /// Used instead of dying when the blast zone does not kill, the player keeps their damage
+    fn return_to_stage(&mut self, context: &mut StepContext, player_i: usize) {
+        self.move_to_respawn_point(context, player_i);
+        self.x_vel = 0.0;
+        self.y_vel = 0.0;
+        self.kb_x_vel = 0.0;
+        self.kb_y_vel = 0.0;
+        self.air_jumps_left = context.fighter.air_jumps;
+        self.fastfalled = false;
+        self.hitstun = 0.0;
+        self.set_action(context, Action::ReSpawn);
+    }
+
     fn check_ledge_grab(&mut self, context: &mut StepContext, ledge_grab_box: &LedgeGrabBox) {
+        if let Some(limit) = context.rules.ledge_grab_limit {
+            if self.ledge_grabs >= limit {
+                return;
+            }
+        }
+
         for (platform_i, platform) in context.surfaces.iter().enumerate() {
-            let left_grab  = platform.left_grab()  && self.check_ledge_collision(ledge_grab_box, platform.left_ledge())  && context.players.iter().all(|x| !x.is_hogging_ledge(platform_i, true));
-            let right_grab = platform.right_grab() && self.check_ledge_collision(ledge_grab_box, platform.right_ledge()) && context.players.iter().all(|x| !x.is_hogging_ledge(platform_i, false));
+            let left_grab  = platform.left_grab()  && self.check_ledge_collision(ledge_grab_box, platform.left_ledge())  && (!context.rules.ledge_hog || context.players.iter().all(|x| !x.is_hogging_ledge(platform_i, true)));
+            let right_grab = platform.right_grab() && self.check_ledge_collision(ledge_grab_box, platform.right_ledge()) && (!context.rules.ledge_hog || context.players.iter().all(|x| !x.is_hogging_ledge(platform_i, false)));
 
             // If both left and right ledges are in range then keep the same direction.
             // This prevents always facing left or right on small surfaces.
@@ -2532,6 +3387,11 @@ impl Player {
                 self.hit_by = None;
                 self.location = Location::GrabbedLedge { platform_i, d_x: -3.0, d_y: -24.0, logic: LedgeLogic::Hog };
                 self.set_action(context, Action::LedgeGrab);
+
+                if self.ledge_grabs == 0 || context.rules.ledge_regrab_intangibility {
+                    self.intangible_timer = context.fighter.ledge_intangibility;
+                }
+                self.ledge_grabs += 1;
             }
         }
     }
@@ -2553,7 +3413,7 @@ impl Player {
         }
     }
 
-    pub fn debug_print(&self, fighter: &Fighter, player_input: &PlayerInput, debug: &DebugPlayer, index: usize) -> Vec<String> {
+    pub fn debug_print(&self, fighter: &Fighter, rules: &Rules, player_input: &PlayerInput, debug: &DebugPlayer, index: usize) -> Vec<String> {
         let mut lines: Vec<String> = vec!();
         if debug.physics {
             lines.push(format!("Player: {}  location: {:?}  x_vel: {:.5}  y_vel: {:.5}  kb_x_vel: {:.5}  kb_y_vel: {:.5}",
@@ -2591,6 +3451,10 @@ impl Player {
 
             lines.push(format!("Player: {}  action: {:?}  frame: {}/{}  frame no restart: {}  IASA: {}",
                 index, action, self.frame, last_action_frame, self.frame_norestart, iasa));
+
+            let stale_moves: Vec<Action> = self.stale_moves.iter().filter_map(|x| Action::from_u64(*x)).collect();
+            lines.push(format!("Player: {}  stale mult: {:.3}  stale moves: {:?}",
+                index, self.stale_mult(rules), stale_moves));
         }
 
         if debug.frame {
@@ -2626,7 +3490,7 @@ impl Player {
         }
     }
 
-    pub fn render(&self, selected_colboxes: HashSet<usize>, fighter_selected: bool, player_selected: bool, debug: DebugPlayer, player_index: usize, player_history: &[Vec<Player>], players: &[Player], fighters: &KeyedContextVec<Fighter>, surfaces: &[Surface]) -> RenderPlayer {
+    pub fn render(&self, selected_colboxes: HashSet<usize>, fighter_selected: bool, player_selected: bool, debug: DebugPlayer, player_index: usize, recent_players: &VecDeque<Vec<Player>>, players: &[Player], fighters: &KeyedContextVec<Fighter>, surfaces: &[Surface]) -> RenderPlayer {
         let fighter_color = graphics::get_team_color3(self.team);
         let fighter = &fighters[self.fighter.as_ref()];
         let mut vector_arrows = vec!();
@@ -2664,6 +3528,24 @@ impl Player {
                 });
             }
         }
+        if debug.sdi_vector {
+            if let Some((x, y)) = self.sdi_offset {
+                let angle = y.atan2(x);
+                vector_arrows.push(VectorArrow {
+                    x: angle.cos(),
+                    y: angle.sin(),
+                    color: [0.0, 0.0, 1.0, 1.0]
+                });
+            }
+            if let Some((x, y)) = self.asdi_offset {
+                let angle = y.atan2(x);
+                vector_arrows.push(VectorArrow {
+                    x: angle.cos(),
+                    y: angle.sin(),
+                    color: [0.0, 1.0, 1.0, 1.0]
+                });
+            }
+        }
 
         let shield = if self.is_shielding() {
             if let &Some(ref shield) = &fighter.shield {
@@ -2679,8 +3561,7 @@ impl Player {
         } else { None };
 
         let mut frames = vec!(self.render_frame(players, fighters, surfaces));
-        let range = player_history.len().saturating_sub(10) .. player_history.len();
-        for players in player_history[range].iter().rev() {
+        for players in recent_players.iter().rev() {
             let player = &players[player_index];
             // handle deleted frames by just skipping it, only encountered when the editor is used.
             if fighter.actions[player.action as usize].frames.len() > player.frame as usize {
@@ -2691,6 +3572,7 @@ impl Player {
         RenderPlayer {
             team:        self.team,
             damage:      self.damage,
+            hp:          None, // filled in by the game as it depends on the rules
             stocks:      self.stocks,
             frame_data:  self.relative_frame(fighter, surfaces),
             particles:   self.particles.clone(),
@@ -2714,6 +3596,21 @@ impl Player {
     }
 
     pub fn hit_particles(&mut self, point: (f32, f32), hitbox: &HitBox) {
+        if let HitboxEffect::None = hitbox.effect { } else {
+            for i in 0..6 {
+                self.particles.push(Particle {
+                    color:       graphics::get_team_color3(self.team),
+                    counter:     0,
+                    counter_max: 30,
+                    x:           point.0,
+                    y:           point.1,
+                    angle:       i as f32 * PI / 3.0,
+                    p_type:      ParticleType::Effect (hitbox.effect.clone()),
+                });
+            }
+        }
+
+        // pushed last as it must be rendered last
         self.particles.push(Particle {
             color:       graphics::get_team_color3(self.team),
             counter:     0,
@@ -2868,6 +3765,7 @@ pub struct RenderPlayer {
     pub team:              usize,
     pub debug:             DebugPlayer,
     pub damage:            f32,
+    pub hp:                Option<f32>, // remaining hp in stamina games
     pub stocks:            Option<u64>,
     pub frame_data:        ActionFrame,
     pub fighter_color:     [f32; 3],
@@ -2926,6 +3824,7 @@ pub struct DebugPlayer {
     pub stick_vector:   bool,
     pub c_stick_vector: bool,
     pub di_vector:      bool,
+    pub sdi_vector:     bool,
     pub hitbox_vectors: bool,
     pub ecb:            bool,
     pub fighter:        RenderFighter,
@@ -2956,7 +3855,12 @@ impl DebugPlayer {
             self.c_stick_vector = !self.c_stick_vector;
         }
         if os_input.key_pressed(VirtualKeyCode::F6) {
-            self.di_vector = !self.di_vector;
+            if os_input.held_shift() {
+                self.sdi_vector = !self.sdi_vector;
+            }
+            else {
+                self.di_vector = !self.di_vector;
+            }
         }
         if os_input.key_pressed(VirtualKeyCode::F7) {
             self.hitbox_vectors = !self.hitbox_vectors;
@@ -2993,6 +3897,7 @@ impl DebugPlayer {
                 stick_vector:   true,
                 c_stick_vector: true,
                 di_vector:      true,
+                sdi_vector:     true,
                 hitbox_vectors: true,
                 ecb:            true,
                 fighter:        RenderFighter::Debug,
@@ -0,0 +1,176 @@
+use crate::collision::ProjectileCollisionResult;
+use crate::graphics;
+use crate::player::Player;
+
+use pf_sandbox_lib::fighter::{Fighter, ActionFrame, CollisionBoxRole, ProjectileDef, ProjectileSpawn};
+use pf_sandbox_lib::stage::Stage;
+
+use treeflection::{Node, NodeRunner, NodeToken, KeyedContextVec};
+
+use std::hash::{Hash, Hasher};
+
+/// A projectile in flight, its behaviour is described by a ProjectileDef belonging to the spawning fighter.
+#[derive(Clone, Default, Serialize, Deserialize, Node)]
+pub struct Projectile {
+    pub fighter:    String, // key of the fighter containing the ProjectileDef
+    pub projectile: usize,  // index into Fighter::projectiles
+    pub owner:      usize,  // index of the player that owns the projectile, changes when reflected
+    pub action:     u64,    // action of the owner when the projectile was fired, used for stale moves
+    pub team:       usize,
+    pub frame:      u64,
+    pub x:          f32,
+    pub y:          f32,
+    pub x_vel:      f32,
+    pub y_vel:      f32,
+    pub face_right: bool,
+    pub hitlist:    Vec<usize>,
+}
+
+impl Projectile {
+    /// Returns None if the spawn refers to a ProjectileDef that doesnt exist, only encountered when the editor is used.
+    pub fn new(player_i: usize, player: &Player, bps: (f32, f32), spawn: &ProjectileSpawn, fighters: &KeyedContextVec<Fighter>) -> Option<Projectile> {
+        let def = fighters[player.fighter.as_ref()].projectiles.get(spawn.projectile)?;
+        Some(Projectile {
+            fighter:    player.fighter.clone(),
+            projectile: spawn.projectile,
+            owner:      player_i,
+            action:     player.action,
+            team:       player.team,
+            frame:      0,
+            x:          bps.0 + player.relative_f(spawn.x),
+            y:          bps.1 + spawn.y,
+            x_vel:      player.relative_f(def.x_vel),
+            y_vel:      def.y_vel,
+            face_right: player.face_right,
+            hitlist:    vec!(),
+        })
+    }
+
+    pub fn def<'a>(&self, fighters: &'a KeyedContextVec<Fighter>) -> Option<&'a ProjectileDef> {
+        fighters.key_to_value(&self.fighter).and_then(|x| x.projectiles.get(self.projectile))
+    }
+
+    /// Projectile frames loop for the lifetime of the projectile
+    fn get_frame<'a>(&self, def: &'a ProjectileDef) -> Option<&'a ActionFrame> {
+        if def.frames.len() > 0 {
+            def.frames.get(self.frame as usize % def.frames.len())
+        } else {
+            None
+        }
+    }
+
+    pub fn relative_f(&self, input: f32) -> f32 {
+        if self.face_right {
+            input
+        }
+        else {
+            input * -1.0
+        }
+    }
+
+    pub fn relative_frame(&self, fighters: &KeyedContextVec<Fighter>) -> ActionFrame {
+        if let Some(frame) = self.def(fighters).and_then(|x| self.get_frame(x)) {
+            let mut frame = frame.clone();
+            for colbox in frame.colboxes.iter_mut() {
+                let (x, y) = colbox.point;
+                colbox.point = (self.relative_f(x), y);
+                if let &mut CollisionBoxRole::Hit (ref mut hitbox) = &mut colbox.role {
+                    if !self.face_right {
+                        hitbox.angle = 180.0 - hitbox.angle
+                    };
+                }
+            }
+            frame
+        } else {
+            ActionFrame::default()
+        }
+    }
+
+    /// returns true if should delete self
+    pub fn step(&mut self, fighters: &KeyedContextVec<Fighter>, stage: &Stage) -> bool {
+        if let Some(def) = self.def(fighters) {
+            self.frame += 1;
+            self.y_vel += def.gravity;
+            self.x += self.x_vel;
+            self.y += self.y_vel;
+            self.frame > def.lifetime || !stage.blast.contains_point(self.x, self.y)
+        } else {
+            true
+        }
+    }
+
+    /// returns true if should delete self
+    pub fn step_collision(&mut self, players: &[Player], fighters: &KeyedContextVec<Fighter>, col_results: &[ProjectileCollisionResult]) -> bool {
+        let def = if let Some(def) = self.def(fighters) { def } else { return true };
+        for col_result in col_results {
+            match col_result {
+                &ProjectileCollisionResult::Hit { player_def_i } => {
+                    self.hitlist.push(player_def_i);
+                    if def.destroy_on_hit {
+                        return true;
+                    }
+                }
+                &ProjectileCollisionResult::Reflect { player_def_i } => {
+                    // the projectile now belongs to the reflecting player and travels in the direction they face
+                    let player_def = &players[player_def_i];
+                    self.owner = player_def_i;
+                    self.action = player_def.action;
+                    self.team = player_def.team;
+                    self.face_right = player_def.face_right;
+                    self.x_vel = self.relative_f(self.x_vel.abs());
+                    self.frame = 0;
+                    self.hitlist.clear();
+                }
+                &ProjectileCollisionResult::Absorb => {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Feeds the state that affects the simulation into the hasher
+    pub fn hash_state<H: Hasher>(&self, hasher: &mut H) {
+        self.fighter.hash(hasher);
+        hasher.write_usize(self.projectile);
+        hasher.write_usize(self.owner);
+        hasher.write_u64(self.action);
+        hasher.write_usize(self.team);
+        hasher.write_u64(self.frame);
+        for value in &[self.x, self.y, self.x_vel, self.y_vel] {
+            hasher.write_u32(value.to_bits());
+        }
+        hasher.write_u8(self.face_right as u8);
+        hasher.write_usize(self.hitlist.len());
+        for player_i in &self.hitlist {
+            hasher.write_usize(*player_i);
+        }
+    }
+
+    pub fn render(&self, fighters: &KeyedContextVec<Fighter>) -> Option<RenderProjectile> {
+        let def = self.def(fighters)?;
+        if def.frames.len() > 0 {
+            Some(RenderProjectile {
+                fighter:    self.fighter.clone(),
+                projectile: self.projectile,
+                frame:      self.frame as usize % def.frames.len(),
+                x:          self.x,
+                y:          self.y,
+                face_right: self.face_right,
+                color:      graphics::get_team_color3(self.team),
+            })
+        } else {
+            None
+        }
+    }
+}
+
+pub struct RenderProjectile {
+    pub fighter:    String,
+    pub projectile: usize,
+    pub frame:      usize,
+    pub x:          f32,
+    pub y:          f32,
+    pub face_right: bool,
+    pub color:      [f32; 3],
+}
@@ -4,13 +4,20 @@ use std::cmp::Ordering;
 
 use chrono::{Local, DateTime};
 
+use pf_sandbox_lib::config::Config;
+use pf_sandbox_lib::fighter::Fighter;
 use pf_sandbox_lib::files;
 use pf_sandbox_lib::input::ControllerInput;
+use pf_sandbox_lib::json_upgrade::engine_version;
 use pf_sandbox_lib::package::Package;
+use pf_sandbox_lib::rules::Rules;
 use pf_sandbox_lib::stage::Stage;
-use crate::game::{Game, PlayerSetup};
+use crate::game::{Game, GameSetup, GameState, PlayerSetup, SuddenDeathRound};
+use crate::history::PlayerHistory;
 use crate::input::Input;
-use crate::player::Player;
+use crate::projectile::Projectile;
+
+use treeflection::{Node, NodeRunner, NodeToken, KeyedContextVec};
 
 pub fn get_replay_names(package: &Package) -> Vec<String> {
     let mut result: Vec<String> = vec!();
@@ -67,46 +74,164 @@ fn get_replay_path(package: &Package, name: &str) -> PathBuf {
     replay_path
 }
 
+/// Replays are not upgraded, so replays saved by another engine version are rejected before attempting to load the rest of the replay
 pub fn load_replay(name: &str, package: &Package) -> Result<Replay, String> {
     let replay_path = get_replay_path(package, name);
-    files::load_struct_compressed(replay_path)
+    let version: ReplayVersion = files::load_struct_compressed(replay_path.clone())?;
+    match version.engine_version {
+        Some(version) if version == engine_version() => files::load_struct_compressed(replay_path),
+        Some(version) => Err(format!("The replay was saved by engine version {} and cannot be played back by engine version {}", version, engine_version())),
+        None          => Err(format!("The replay was saved by an older version of PF Sandbox and cannot be played back by engine version {}", engine_version())),
+    }
 }
 
-pub fn save_replay(replay: &Replay, package: &Package) {
+pub fn save_replay(replay: &Replay, package: &Package, config: &Config) {
     let replay_path = get_replay_path(package, replay.timestamp.to_rfc2822().as_ref()); // TODO: could still collide under strange circumstances: check and handle
-    files::save_struct_compressed(replay_path, &replay);
+    if config.input_only_replays {
+        files::save_struct_compressed(replay_path, &replay.input_only());
+    } else {
+        files::save_struct_compressed(replay_path, &replay);
+    }
+}
+
+/// Only the engine version of a replay, missing from replays saved before it was recorded
+#[derive(Deserialize)]
+struct ReplayVersion {
+    engine_version: Option<u64>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Replay {
+    pub engine_version:       u64,
     pub init_seed:            u64,
     pub timestamp:            DateTime<Local>,
     pub input_history:        Vec<Vec<ControllerInput>>,
-    pub player_history:       Vec<Vec<Player>>,
+    pub player_history:       PlayerHistory,
     pub stage_history:        Vec<Stage>,
+    pub projectile_history:   Vec<Vec<Projectile>>,
+    pub checksums:            Vec<u64>, // used to detect when playback no longer matches the recording
     pub selected_controllers: Vec<usize>,
     pub selected_players:     Vec<PlayerSetup>,
     pub selected_ais:         Vec<usize>,
     pub selected_stage:       String,
+    pub sudden_death:         Option<SuddenDeathRound>,
+    pub snapshot:             PackageSnapshot,
 }
 
 impl Replay {
     pub fn new(game: &Game, input: &Input) -> Replay {
         let selected_players = game.players.iter().map(|x| PlayerSetup {
-            fighter: x.fighter.clone(),
-            team:    x.team,
+            fighter:  x.fighter.clone(),
+            team:     x.team,
+            handicap: x.handicap.clone(),
         }).collect();
 
         Replay {
+            engine_version:       engine_version(),
             init_seed:            game.init_seed.clone(),
             timestamp:            Local::now(),
             input_history:        input.get_history(),
             player_history:       game.player_history.clone(),
             stage_history:        game.stage_history.clone(),
+            projectile_history:   game.projectile_history.clone(),
+            checksums:            game.checksums.clone(),
             selected_controllers: game.selected_controllers.clone(),
             selected_ais:         game.selected_ais.clone(),
             selected_stage:       game.selected_stage.clone(),
+            sudden_death:         game.sudden_death.clone(),
+            snapshot:             game.snapshot.clone(),
             selected_players
         }
     }
+
+    /// Setup to play back the replay, uses the package data the replay was recorded with when use_snapshot is true
+    pub fn into_game_setup(self, use_snapshot: bool) -> GameSetup {
+        GameSetup {
+            init_seed:          self.init_seed,
+            input_history:      self.input_history,
+            player_history:     self.player_history,
+            stage_history:      self.stage_history,
+            projectile_history: self.projectile_history,
+            checksums:          self.checksums,
+            controllers:        self.selected_controllers,
+            players:            self.selected_players,
+            ais:                self.selected_ais,
+            stage:              self.selected_stage,
+            state:              GameState::ReplayForwards,
+            sudden_death:       self.sudden_death,
+            snapshot:           if use_snapshot { Some(self.snapshot) } else { None },
+        }
+    }
+
+    /// The replay without the player, stage and projectile history.
+    /// The game is re-simulated from the inputs when played back, which keeps the file small
+    /// and allows playing back against an edited package.
+    pub fn input_only(&self) -> Replay {
+        Replay {
+            player_history:     PlayerHistory::default(),
+            stage_history:      vec!(),
+            projectile_history: vec!(),
+            .. self.clone()
+        }
+    }
+}
+
+/// The package data used by a game, so that a replay can be re-simulated after the package is edited
+#[derive(Clone, Serialize, Deserialize, Node)]
+pub struct PackageSnapshot {
+    pub rules:     Rules,
+    pub stage_key: String,
+    pub stage:     Stage,
+    pub fighters:  KeyedContextVec<Fighter>, // only the fighters used in the game
+}
+
+impl Default for PackageSnapshot {
+    fn default() -> PackageSnapshot {
+        PackageSnapshot {
+            rules:     Rules::default(),
+            stage_key: String::new(),
+            stage:     Stage::default(),
+            fighters:  KeyedContextVec::new(),
+        }
+    }
+}
+
+impl PackageSnapshot {
+    /// Taken as the game starts, so edits made to the package while the game runs are not recorded
+    pub fn new(package: &Package, setup: &GameSetup) -> PackageSnapshot {
+        let mut fighters = KeyedContextVec::new();
+        for player in &setup.players {
+            if !fighters.contains_key(&player.fighter) {
+                if let Some(fighter) = package.fighters.key_to_value(&player.fighter) {
+                    fighters.push(player.fighter.clone(), fighter.clone());
+                }
+            }
+        }
+
+        PackageSnapshot {
+            rules:     package.rules.clone(),
+            stage_key: setup.stage.clone(),
+            stage:     package.stages[setup.stage.as_ref()].clone(),
+            fighters,
+        }
+    }
+
+    /// Overwrite the package data with the snapshot, fighters and stages missing from the package are added
+    pub fn apply(&self, package: &mut Package) {
+        package.rules = self.rules.clone();
+
+        if package.stages.contains_key(&self.stage_key) {
+            package.stages[self.stage_key.as_ref()] = self.stage.clone();
+        } else {
+            package.stages.push(self.stage_key.clone(), self.stage.clone());
+        }
+
+        for (key, fighter) in self.fighters.key_value_iter() {
+            if package.fighters.contains_key(key) {
+                package.fighters[key.as_ref()] = fighter.clone();
+            } else {
+                package.fighters.push(key.clone(), fighter.clone());
+            }
+        }
+    }
 }
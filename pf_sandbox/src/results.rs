@@ -26,13 +26,15 @@ pub struct PlayerResult {
     pub team:             usize,
     pub controller:       usize,
     pub place:            usize,
-    pub kills:            Vec<DeathRecord>,
-    pub deaths:           Vec<DeathRecord>,
+    pub kills:            Vec<DeathRecord>, // player is the player that was killed
+    pub deaths:           Vec<DeathRecord>, // player is the player that got the kill
+    pub team_kills:       Option<usize>, // combined with teammates, None when teams are off
+    pub team_deaths:      Option<usize>,
     pub lcancel_percent:  f32,
 }
 
 /// An individual players results: unprocessed
-#[derive(Debug, Clone, Default, Serialize, Deserialize, Node)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Node)]
 pub struct RawPlayerResult {
     pub team:             usize,
     pub deaths:           Vec<DeathRecord>,
@@ -42,8 +44,8 @@ pub struct RawPlayerResult {
     pub ended_as_fighter: Option<String>,
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, Node)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Node)]
 pub struct DeathRecord {
-    pub player: Option<usize>, // None indicates self-destruct
+    pub player: Option<usize>, // None indicates self-destruct or that the kill credit expired
     pub frame:  usize,
 }
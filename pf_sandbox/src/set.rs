@@ -0,0 +1,162 @@
+use pf_sandbox_lib::package::Package;
+use pf_sandbox_lib::rules::Rules;
+use crate::results::PlayerResult;
+
+/// A best-of-N set, kept by the menu between games.
+/// Players are identified by their team so that doubles sets work the same as singles.
+#[derive(Clone)]
+pub struct Set {
+    pub best_of: u64,
+    pub teams:   Vec<usize>, // teams taking part in the set
+    pub games:   Vec<SetGame>,
+    pub stages:  Vec<SetStage>, // stages available for the next game
+    pub turns:   Vec<SetTurn>, // remaining strikes and picks for the next game, the first entry is the current turn
+    pub picked:  Option<String>, // the stage the next game will be played on
+}
+
+#[derive(Clone)]
+pub struct SetGame {
+    pub stage:    String,
+    pub winner:   usize, // team
+    pub fighters: Vec<(usize, String)>, // team and fighter of each player
+}
+
+#[derive(Clone)]
+pub struct SetStage {
+    pub key:       String,
+    pub struck_by: Option<usize>, // team that struck or banned the stage
+}
+
+#[derive(Clone)]
+pub struct SetTurn {
+    pub team: usize,
+    pub kind: SetTurnKind,
+}
+
+#[derive(Clone)]
+pub enum SetTurnKind {
+    Strike,
+    Pick,
+}
+
+impl Set {
+    /// Starts a set with the first game stage chosen by striking from the starter stages.
+    /// Teams take turns striking: the first team strikes one stage then each team strikes two until one stage remains.
+    pub fn new(rules: &Rules, package: &Package, teams: Vec<usize>) -> Set {
+        let stages = Set::legal_stages(package, &rules.starter_stages);
+
+        let mut turns = vec!();
+        let mut team_i = 0;
+        let mut strikes = 1;
+        while turns.len() + 1 < stages.len() && teams.len() > 0 {
+            for _ in 0..strikes {
+                if turns.len() + 1 < stages.len() {
+                    turns.push(SetTurn { team: teams[team_i], kind: SetTurnKind::Strike });
+                }
+            }
+            team_i = (team_i + 1) % teams.len();
+            strikes = 2;
+        }
+
+        let mut set = Set {
+            best_of: rules.best_of,
+            games:   vec!(),
+            picked:  None,
+            teams,
+            stages,
+            turns,
+        };
+        set.resolve_pick();
+        set
+    }
+
+    /// The stages with the passed keys, or every stage when there are none.
+    /// Keys of stages not in the package are ignored.
+    fn legal_stages(package: &Package, keys: &[String]) -> Vec<SetStage> {
+        let keys: Vec<String> = if keys.len() == 0 {
+            package.stages.key_value_iter().map(|(key, _)| key.clone()).collect()
+        } else {
+            keys.iter().filter(|key| package.stages.contains_key(*key)).cloned().collect()
+        };
+        keys.into_iter().map(|key| SetStage { key, struck_by: None }).collect()
+    }
+
+    pub fn current_turn(&self) -> Option<&SetTurn> {
+        self.turns.first()
+    }
+
+    /// Strike or pick the stage at stage_i for the current turn, does nothing if the stage was already struck
+    pub fn choose(&mut self, stage_i: usize) {
+        if self.picked.is_some() || self.stages.get(stage_i).map_or(true, |x| x.struck_by.is_some()) {
+            return;
+        }
+
+        if self.turns.len() > 0 {
+            let turn = self.turns.remove(0);
+            match turn.kind {
+                SetTurnKind::Strike => { self.stages[stage_i].struck_by = Some(turn.team) }
+                SetTurnKind::Pick   => { self.picked = Some(self.stages[stage_i].key.clone()) }
+            }
+        }
+        self.resolve_pick();
+    }
+
+    /// When there are no turns left the first remaining stage is played on
+    fn resolve_pick(&mut self) {
+        if self.turns.len() == 0 && self.picked.is_none() {
+            self.picked = self.stages.iter().find(|x| x.struck_by.is_none()).map(|x| x.key.clone());
+        }
+    }
+
+    /// Record the result of the game played on the picked stage.
+    /// If the set continues, the winner bans counterpick_bans stages then the last placing team picks the next stage.
+    pub fn record_game(&mut self, rules: &Rules, package: &Package, results: &[PlayerResult]) {
+        let stage = match self.picked.take() {
+            Some(stage) => stage,
+            None        => return,
+        };
+
+        // results are sorted by place
+        let winner = match results.first() {
+            Some(result) => result.team,
+            None         => return,
+        };
+        let loser = results.last().unwrap().team;
+
+        self.games.push(SetGame {
+            stage,
+            winner,
+            fighters: results.iter().map(|x| (x.team, x.fighter.clone())).collect(),
+        });
+
+        if !self.is_finished() {
+            let mut keys = rules.starter_stages.clone();
+            keys.extend(rules.counterpick_stages.iter().cloned());
+            if rules.starter_stages.len() == 0 {
+                keys.clear(); // every stage is a starter, so every stage is legal
+            }
+            self.stages = Set::legal_stages(package, &keys);
+
+            self.turns.clear();
+            let bans = (rules.counterpick_bans as usize).min(self.stages.len().saturating_sub(1));
+            for _ in 0..bans {
+                self.turns.push(SetTurn { team: winner, kind: SetTurnKind::Strike });
+            }
+            self.turns.push(SetTurn { team: loser, kind: SetTurnKind::Pick });
+            self.resolve_pick();
+        }
+    }
+
+    pub fn wins(&self, team: usize) -> u64 {
+        self.games.iter().filter(|x| x.winner == team).count() as u64
+    }
+
+    /// The team that has won more than half of the best_of games
+    pub fn winner(&self) -> Option<usize> {
+        self.teams.iter().cloned().find(|team| self.wins(*team) * 2 > self.best_of)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.winner().is_some() || self.games.len() as u64 >= self.best_of
+    }
+}
@@ -1,4 +1,4 @@
-use pf_sandbox_lib::fighter::{LinkType, CollisionBox, CollisionBoxLink, ColboxOrLink};
+use pf_sandbox_lib::fighter::{LinkType, CollisionBox, CollisionBoxLink, ColboxOrLink, ActionFrame};
 use pf_sandbox_lib::geometry::Rect;
 use pf_sandbox_lib::package::Package;
 use pf_sandbox_lib::stage::Surface;
@@ -432,29 +432,34 @@ impl Buffers {
 
     pub fn new_fighter_frame(device: &Device, package: &Package, fighter: &str, action: usize, frame: usize) -> Option<Buffers> {
         let frames = &package.fighters[fighter].actions[action].frames;
-        if let Some(frame) = frames.get(frame) {
-            let mut vertices: Vec<Vertex> = vec!();
-            let mut indices: Vec<u16> = vec!();
-            let mut index_count = 0;
-
-            for colbox_or_link in frame.get_colboxes_and_links() {
-                match colbox_or_link {
-                    ColboxOrLink::Colbox (ref colbox) => {
-                        let render_id = graphics::get_render_id(&colbox.role);
-                        Buffers::gen_colbox(&mut vertices, &mut indices, colbox, &mut index_count, render_id);
-                    }
-                    ColboxOrLink::Link (ref link) => {
-                        let colbox1 = &frame.colboxes[link.one];
-                        let colbox2 = &frame.colboxes[link.two];
-                        Buffers::gen_link(&mut vertices, &mut indices, link, colbox1, colbox2, &mut index_count);
-                    }
+        frames.get(frame).map(|frame| Buffers::new_frame(device, frame))
+    }
+
+    pub fn new_projectile_frame(device: &Device, package: &Package, fighter: &str, projectile: usize, frame: usize) -> Option<Buffers> {
+        let projectile = package.fighters[fighter].projectiles.get(projectile)?;
+        projectile.frames.get(frame).map(|frame| Buffers::new_frame(device, frame))
+    }
+
+    fn new_frame(device: &Device, frame: &ActionFrame) -> Buffers {
+        let mut vertices: Vec<Vertex> = vec!();
+        let mut indices: Vec<u16> = vec!();
+        let mut index_count = 0;
+
+        for colbox_or_link in frame.get_colboxes_and_links() {
+            match colbox_or_link {
+                ColboxOrLink::Colbox (ref colbox) => {
+                    let render_id = graphics::get_render_id(&colbox.role);
+                    Buffers::gen_colbox(&mut vertices, &mut indices, colbox, &mut index_count, render_id);
+                }
+                ColboxOrLink::Link (ref link) => {
+                    let colbox1 = &frame.colboxes[link.one];
+                    let colbox2 = &frame.colboxes[link.two];
+                    Buffers::gen_link(&mut vertices, &mut indices, link, colbox1, colbox2, &mut index_count);
                 }
             }
-
-            Some(Buffers::new(device, &vertices, &indices))
-        } else {
-            None
         }
+
+        Buffers::new(device, &vertices, &indices)
     }
 }
 
@@ -3,13 +3,15 @@
 mod buffers;
 
 use buffers::{ColorVertex, ColorBuffers, Vertex, Buffers};
-use crate::game::{GameState, RenderEntity, RenderGame};
+use crate::game::{GameState, RenderEntity, RenderGame, RenderTimeline, MAX_PLAYERS, TIMELINE_AREA};
+use crate::history::TimelineEventKind;
 use crate::graphics::{self, GraphicsMessage, Render, RenderType};
 use crate::menu::{RenderMenu, RenderMenuState, PlayerSelect, PlayerSelectUi};
 use crate::particle::ParticleType;
 use crate::player::{RenderFighter, RenderPlayer, RenderPlayerFrame, DebugPlayer};
 use crate::results::PlayerResult;
-use pf_sandbox_lib::fighter::{Action, ECB, CollisionBoxRole, ActionFrame};
+use crate::set::{Set, SetTurn, SetTurnKind};
+use pf_sandbox_lib::fighter::{Action, ECB, CollisionBoxRole, ActionFrame, HitboxEffect};
 use pf_sandbox_lib::geometry::Rect;
 use pf_sandbox_lib::json_upgrade;
 use pf_sandbox_lib::package::{Package, PackageUpdate, Verify};
@@ -447,8 +449,10 @@ impl WgpuGraphics {
             }
         }
         let distance = (self.width / (players + 1)) as f32;
+        // shrink the HUD when there are more players than fit at full size
+        let scale = 4.0 / players.max(4) as f32;
 
-        let mut location = -100.0;
+        let mut location = -100.0 * scale;
         for entity in entities {
             if let &RenderEntity::Player(ref player) = entity {
                 location += distance;
@@ -472,17 +476,22 @@ impl WgpuGraphics {
                             self.glyph_brush.queue(Section {
                                 text: stocks_string.as_ref(),
                                 color,
-                                screen_position: (location + 10.0, self.height as f32 - 130.0),
-                                scale: GlyphScale::uniform(22.0),
+                                screen_position: (location + 10.0 * scale, self.height as f32 - 130.0 * scale),
+                                scale: GlyphScale::uniform(22.0 * scale.max(0.75)),
                                 .. Section::default()
                             });
                         }
 
+                        let damage = if let Some(hp) = player.hp {
+                            format!("{:.0}HP", hp)
+                        } else {
+                            format!("{}%", player.damage)
+                        };
                         self.glyph_brush.queue(Section {
-                            text: format!("{}%", player.damage).as_ref(),
+                            text: damage.as_ref(),
                             color,
-                            screen_position: (location, self.height as f32 - 117.0),
-                            scale: GlyphScale::uniform(110.0),
+                            screen_position: (location, self.height as f32 - 117.0 * scale),
+                            scale: GlyphScale::uniform(110.0 * scale),
                             .. Section::default()
                         });
                     }
@@ -509,6 +518,47 @@ impl WgpuGraphics {
         });
     }
 
+    fn game_timeline_render(&self, rpass: &mut RenderPass, timeline: &RenderTimeline) {
+        let left   = TIMELINE_AREA.left();
+        let right  = TIMELINE_AREA.right();
+        let bot    = TIMELINE_AREA.bot();
+        let top    = TIMELINE_AREA.top();
+        let frame_x = |frame: usize| left + (right - left) * frame as f32 / timeline.last_frame.max(1) as f32;
+        let marker = |frame: usize, width: f32, extend: f32| {
+            let x = frame_x(frame);
+            Rect { x1: x - width, y1: bot - extend, x2: x + width, y2: top + extend }
+        };
+
+        // later rects are drawn over earlier ones
+        self.render_screen_rect(rpass, TIMELINE_AREA.clone(), [0.2, 0.2, 0.2, 1.0]);
+        self.render_screen_rect(rpass, Rect { x1: left, y1: bot, x2: frame_x(timeline.current_frame), y2: top }, [0.4, 0.4, 0.4, 1.0]);
+        for event in &timeline.events {
+            match event.kind {
+                TimelineEventKind::Hit   => self.render_screen_rect(rpass, marker(event.frame, 0.001, 0.0),  [1.0, 1.0, 1.0, 1.0]),
+                TimelineEventKind::Death => self.render_screen_rect(rpass, marker(event.frame, 0.003, 0.01), [1.0, 0.0, 0.0, 1.0]),
+            }
+        }
+        self.render_screen_rect(rpass, marker(timeline.saved_frame,   0.002, 0.01), [0.0, 0.5, 1.0, 1.0]);
+        self.render_screen_rect(rpass, marker(timeline.current_frame, 0.002, 0.02), [1.0, 1.0, 0.0, 1.0]);
+    }
+
+    /// Draw a rect positioned in screen coordinates ranging from -1.0 to 1.0, in front of everything in the game
+    fn render_screen_rect(&self, rpass: &mut RenderPass, rect: Rect, color: [f32; 4]) {
+        let uniform = Uniform {
+            edge_color:     color,
+            color,
+            transformation: Matrix4::identity().into(),
+        };
+        let bind_group = self.new_bind_group(uniform);
+        let buffers = Buffers::rect_buffers(&self.device, rect);
+
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &bind_group, &[]);
+        rpass.set_index_buffer(&buffers.index, 0);
+        rpass.set_vertex_buffers(0, &[(&buffers.vertex, 0)]);
+        rpass.draw_indexed(0 .. buffers.index_count, 0, 0 .. 1);
+    }
+
     fn debug_lines_render(&mut self, lines: &[String]) {
         if lines.len() > 1 {
             for (i, line) in lines.iter().enumerate() {
@@ -629,6 +679,10 @@ impl WgpuGraphics {
             self.command_render(command_output);
         }
 
+        if let Some(ref timeline) = render.timeline {
+            self.game_timeline_render(rpass, timeline);
+        }
+
         let pan = render.camera.pan;
 
         match render.state {
@@ -816,6 +870,29 @@ impl WgpuGraphics {
                                 let color = [0.5, 0.5, 0.5, 1.5];
                                 self.render_buffers(&self.pipeline, rpass, &render, jump_buffers.clone(), &transformation, color, color) // TODO: Invert
                             }
+                            &ParticleType::Effect (ref effect) => {
+                                let (color, buffers) = match effect {
+                                    &HitboxEffect::Fire            => ([1.0, 0.4, 0.0, 1.0], &triangle_buffers),
+                                    &HitboxEffect::Electric { .. } => ([1.0, 1.0, 0.2, 1.0], &triangle_buffers),
+                                    &HitboxEffect::Sleep    { .. } => ([1.0, 0.6, 0.9, 0.7], &jump_buffers),
+                                    &HitboxEffect::Reverse         => ([0.6, 0.2, 1.0, 1.0], &triangle_buffers),
+                                    &HitboxEffect::Stun     { .. } => ([1.0, 1.0, 0.5, 1.0], &jump_buffers),
+                                    &HitboxEffect::Freeze   { .. } => ([0.6, 0.9, 1.0, 1.0], &triangle_buffers),
+                                    &HitboxEffect::None            => ([c[0], c[1], c[2], 1.0], &triangle_buffers),
+                                };
+                                // particles burst outwards from the hit point
+                                let distance = 2.0 + particle.counter_mult() * 6.0;
+                                let size = 1.5 * (1.0 - particle.counter_mult());
+                                let size = Matrix4::from_nonuniform_scale(size, size, 1.0);
+                                let rotate = Matrix4::from_angle_z(Rad(particle.angle));
+                                let position = Matrix4::from_translation(Vector3::new(
+                                    particle.x + particle.angle.cos() * distance + pan.0,
+                                    particle.y + particle.angle.sin() * distance + pan.1,
+                                    z_particle_fg
+                                ));
+                                let transformation = position * rotate * size;
+                                self.render_buffers(&self.pipeline, rpass, &render, buffers.clone(), &transformation, color, color)
+                            }
                         }
                     }
 
@@ -839,6 +916,17 @@ impl WgpuGraphics {
                         _ => { }
                     }
                 }
+                &RenderEntity::Projectile (ref projectile) => {
+                    if let Some(buffers) = Buffers::new_projectile_frame(&self.device, &self.package.as_ref().unwrap(), &projectile.fighter, projectile.projectile, projectile.frame) {
+                        let dir      = Matrix4::from_nonuniform_scale(if projectile.face_right { 1.0 } else { -1.0 }, 1.0, 1.0);
+                        let position = Matrix4::from_translation(Vector3::new(projectile.x + pan.0, projectile.y + pan.1, z_player));
+                        let transformation = position * dir;
+                        let c = projectile.color;
+                        let edge_color = [c[0], c[1], c[2], 1.0];
+                        let color = [0.9, 0.9, 0.9, 1.0];
+                        self.render_buffers(&self.pipeline, rpass, &render, buffers, &transformation, edge_color, color);
+                    }
+                }
                 &RenderEntity::RectOutline (ref render_rect) => {
                     let transformation = Matrix4::from_translation(Vector3::new(pan.0, pan.1, 0.0));
                     let color = render_rect.color;
@@ -919,28 +1007,22 @@ impl WgpuGraphics {
                             .. Section::default()
                         });
                     }
-                    1 => {
-                        self.draw_fighter_selector(&mut entities, &plugged_in_selections, 0, -0.9, -0.8, 0.9, 0.9);
-
-                    }
-                    2 => {
-                        self.draw_fighter_selector(&mut entities, &plugged_in_selections, 0, -0.9, -0.8, 0.0, 0.9);
-                        self.draw_fighter_selector(&mut entities, &plugged_in_selections, 1,  0.0, -0.8, 0.9, 0.9);
-                    }
-                    3 => {
-                        self.draw_fighter_selector(&mut entities, &plugged_in_selections, 0, -0.9, -0.8, 0.0, 0.0);
-                        self.draw_fighter_selector(&mut entities, &plugged_in_selections, 1,  0.0, -0.8, 0.9, 0.0);
-                        self.draw_fighter_selector(&mut entities, &plugged_in_selections, 2, -0.9,  0.0, 0.0, 0.9);
-                    }
-                    4 => {
-                        self.draw_fighter_selector(&mut entities, &plugged_in_selections, 0, -0.9, -0.8, 0.0, 0.0);
-                        self.draw_fighter_selector(&mut entities, &plugged_in_selections, 1,  0.0, -0.8, 0.9, 0.0);
-                        self.draw_fighter_selector(&mut entities, &plugged_in_selections, 2, -0.9,  0.0, 0.0, 0.9);
-                        self.draw_fighter_selector(&mut entities, &plugged_in_selections, 3,  0.0,  0.0, 0.9, 0.9);
+                    len if len <= MAX_PLAYERS => {
+                        // lay the selectors out in a grid that is as square as possible
+                        let columns = (len as f32).sqrt().ceil() as usize;
+                        let rows = (len + columns - 1) / columns;
+                        let width  = 1.8 / columns as f32;
+                        let height = 1.7 / rows as f32;
+                        for i in 0..len {
+                            let start_x = -0.9 + width  * (i % columns) as f32;
+                            let start_y = -0.8 + height * (i / columns) as f32;
+                            self.draw_fighter_selector(&mut entities, &plugged_in_selections, i, start_x, start_y, start_x + width, start_y + height);
+                        }
                     }
                     _ => {
+                        let text = format!("Currently only supports up to {} controllers. Please unplug some.", MAX_PLAYERS);
                         self.glyph_brush.queue(Section {
-                            text: "Currently only supports up to 4 controllers. Please unplug some.",
+                            text: &text,
                             color: [1.0, 1.0, 1.0, 1.0],
                             screen_position: (100.0, 100.0),
                             scale: GlyphScale::uniform(30.0),
@@ -954,7 +1036,14 @@ impl WgpuGraphics {
                 self.draw_stage_selector(&mut entities, selection);
                 self.draw_package_banner(&render.package_verify, command_output);
             }
-            RenderMenuState::GameResults { results, replay_saved } => {
+            RenderMenuState::SetStageSelect (set, selection) => {
+                self.draw_set_stage_selector(&mut entities, &set, selection);
+                self.draw_package_banner(&render.package_verify, command_output);
+            }
+            RenderMenuState::SetSummary (set) => {
+                self.draw_set_summary(&set);
+            }
+            RenderMenuState::GameResults { results, replay_saved, set } => {
                 let max = results.len() as f32;
                 for (i, result) in results.iter().enumerate() {
                     let i = i as f32;
@@ -962,6 +1051,16 @@ impl WgpuGraphics {
                     self.draw_player_result(result, start_x);
                 }
 
+                if let Some(set) = set {
+                    self.glyph_brush.queue(Section {
+                        text: &set_score(&set),
+                        color: [1.0, 1.0, 1.0, 1.0],
+                        screen_position: (30.0, self.height as f32 - 70.0),
+                        scale: GlyphScale::uniform(30.0),
+                        .. Section::default()
+                    });
+                }
+
                 if replay_saved {
                     self.glyph_brush.queue(Section {
                         text: "Replay saved!",
@@ -1163,6 +1262,14 @@ impl WgpuGraphics {
                 .. Section::default()
             });
         }
+
+        self.glyph_brush.queue(Section {
+            text: "A: Play with current package    X: Play with package from replay",
+            color: [1.0, 1.0, 1.0, 1.0],
+            screen_position: (100.0, self.height as f32 - 50.0),
+            scale: GlyphScale::uniform(26.0),
+            .. Section::default()
+        });
     }
 
     // TODO: Rewrite text rendering to be part of scene instead of just plastered on top
@@ -1208,12 +1315,14 @@ impl WgpuGraphics {
                 [0.5, 0.5, 0.5, 1.0]
             };
             let name = match selection.ui {
-                PlayerSelectUi::CpuAi        (_) => format!("CPU AI"),
-                PlayerSelectUi::CpuFighter   (_) => format!("CPU Fighter"),
-                PlayerSelectUi::HumanFighter (_) => format!("Port #{}", controller_i+1),
-                PlayerSelectUi::HumanTeam    (_) => format!("Port #{} Team", controller_i+1),
-                PlayerSelectUi::CpuTeam      (_) => format!("CPU Team"),
-                PlayerSelectUi::HumanUnplugged   => unreachable!()
+                PlayerSelectUi::CpuAi         (_) => format!("CPU AI"),
+                PlayerSelectUi::CpuFighter    (_) => format!("CPU Fighter"),
+                PlayerSelectUi::HumanFighter  (_) => format!("Port #{}", controller_i+1),
+                PlayerSelectUi::HumanTeam     (_) => format!("Port #{} Team", controller_i+1),
+                PlayerSelectUi::CpuTeam       (_) => format!("CPU Team"),
+                PlayerSelectUi::HumanHandicap (_) => format!("Port #{} Handicap", controller_i+1),
+                PlayerSelectUi::CpuHandicap   (_) => format!("CPU Handicap"),
+                PlayerSelectUi::HumanUnplugged    => unreachable!()
             };
             self.glyph_brush.queue(Section {
                 text: name.as_ref(),
@@ -1230,11 +1339,13 @@ impl WgpuGraphics {
             PlayerSelectUi::HumanFighter (_) => {
                 options.extend(fighters.iter().map(|x| x.name.clone()));
                 options.push(String::from("Change Team"));
+                options.push(String::from("Handicap"));
                 options.push(String::from("Add CPU"));
             }
             PlayerSelectUi::CpuFighter (_) => {
                 options.extend(fighters.iter().map(|x| x.name.clone()));
                 options.push(String::from("Change Team"));
+                options.push(String::from("Handicap"));
                 options.push(String::from("Change AI"));
                 options.push(String::from("Remove CPU"));
             }
@@ -1246,6 +1357,15 @@ impl WgpuGraphics {
                 options.extend(graphics::get_colors().iter().map(|x| x.name.clone()));
                 options.push(String::from("Return"));
             }
+            PlayerSelectUi::HumanHandicap (_) |
+            PlayerSelectUi::CpuHandicap (_) => {
+                let handicap = &selection.handicap;
+                options.push(format!("Damage: {}%", handicap.damage));
+                options.push(format!("Damage Taken: x{}", handicap.damage_taken_mult));
+                options.push(format!("Knockback Dealt: x{}", handicap.knockback_dealt_mult));
+                options.push(format!("Extra Stocks: {}", handicap.extra_stocks));
+                options.push(String::from("Return"));
+            }
             PlayerSelectUi::CpuAi (_) => {
                 options.push(String::from("Return"));
             }
@@ -1314,6 +1434,7 @@ impl WgpuGraphics {
                         team:              selection.team,
                         debug:             DebugPlayer::default(),
                         damage:            0.0,
+                        hp:                None,
                         stocks:            None,
                         frame_data:        ActionFrame::default(),
                         fighter_color:     graphics::get_team_color3(selection.team),
@@ -1398,6 +1519,103 @@ impl WgpuGraphics {
         }
     }
 
+    fn draw_set_stage_selector(&mut self, entities: &mut Vec<MenuEntityAndBindGroup>, set: &Set, selection: usize) {
+        let title = match set.current_turn() {
+            Some(&SetTurn { team, kind: SetTurnKind::Strike }) if set.games.len() == 0 => format!("Game 1: {} strike a stage", graphics::get_team_name(team)),
+            Some(&SetTurn { team, kind: SetTurnKind::Strike }) => format!("Game {}: {} ban a stage", set.games.len() + 1, graphics::get_team_name(team)),
+            Some(&SetTurn { team, kind: SetTurnKind::Pick })   => format!("Game {}: {} pick a stage", set.games.len() + 1, graphics::get_team_name(team)),
+            None => format!("Game {}", set.games.len() + 1),
+        };
+        let title_color = set.current_turn().map_or([1.0, 1.0, 1.0, 1.0], |x| graphics::get_team_color4(x.team));
+        self.glyph_brush.queue(Section {
+            text: &title,
+            color: title_color,
+            screen_position: (100.0, 4.0),
+            scale: GlyphScale::uniform(50.0),
+            .. Section::default()
+        });
+
+        let stages = &self.package.as_ref().unwrap().stages;
+        for (stage_i, set_stage) in set.stages.iter().enumerate() {
+            let stage = &stages[set_stage.key.as_ref()];
+            let (text, color) = match set_stage.struck_by {
+                Some(team) => (format!("{} (struck by {})", stage.name, graphics::get_team_name(team)), [0.5, 0.5, 0.5, 1.0]),
+                None       => (stage.name.clone(), [1.0, 1.0, 1.0, 1.0]),
+            };
+            let size = 26.0; // TODO: determine from width/height of screen and start/end pos
+            let x_offset = if stage_i == selection { 0.05 } else { 0.0 };
+            let x = self.width as f32 * (0.1 + x_offset);
+            let y = self.height as f32 * 0.1 + stage_i as f32 * 50.0;
+            self.glyph_brush.queue(Section {
+                text: &text,
+                color,
+                screen_position: (x, y),
+                scale: GlyphScale::uniform(size),
+                .. Section::default()
+            });
+
+            if stage_i == selection {
+                let zoom_divider = 100.0;
+                let zoom = 1.0 / zoom_divider;
+                let y = -0.2 * zoom_divider;
+
+                let camera   = Matrix4::from_nonuniform_scale(zoom, zoom * self.aspect_ratio(), 1.0);
+                let position = Matrix4::from_translation(Vector3::new(1.0, y, 0.0));
+                let transformation = camera * position;
+                let uniform = ColorUniform { transformation: transformation.into() };
+
+                let bind_group = self.new_bind_group(uniform);
+                let entity = MenuEntity::Stage(set_stage.key.clone());
+                entities.push(MenuEntityAndBindGroup { bind_group, entity });
+
+                let bind_group = self.new_bind_group(uniform);
+                let entity = MenuEntity::StageFill(set_stage.key.clone());
+                entities.push(MenuEntityAndBindGroup { bind_group, entity });
+            }
+        }
+
+        self.glyph_brush.queue(Section {
+            text: &set_score(set),
+            color: [1.0, 1.0, 1.0, 1.0],
+            screen_position: (30.0, self.height as f32 - 70.0),
+            scale: GlyphScale::uniform(30.0),
+            .. Section::default()
+        });
+    }
+
+    fn draw_set_summary(&mut self, set: &Set) {
+        let (title, title_color) = match set.winner() {
+            Some(team) => (format!("{} wins the set!", graphics::get_team_name(team)), graphics::get_team_color4(team)),
+            None       => (String::from("Set complete"), [1.0, 1.0, 1.0, 1.0]),
+        };
+        self.glyph_brush.queue(Section {
+            text: &title,
+            color: title_color,
+            screen_position: (100.0, 4.0),
+            scale: GlyphScale::uniform(50.0),
+            .. Section::default()
+        });
+
+        let package = self.package.as_ref().unwrap();
+        let mut lines = vec!();
+        for (game_i, game) in set.games.iter().enumerate() {
+            let fighters: Vec<String> = game.fighters.iter().map(|(team, fighter)| format!("{} {}", graphics::get_team_name(*team), package.fighters[fighter.as_ref()].name)).collect();
+            let text = format!("Game {}: {} won on {} - {}", game_i + 1, graphics::get_team_name(game.winner), package.stages[game.stage.as_ref()].name, fighters.join(", "));
+            lines.push((text, graphics::get_team_color4(game.winner)));
+        }
+        lines.push((set_score(set), [1.0, 1.0, 1.0, 1.0]));
+
+        for (line_i, (text, color)) in lines.iter().enumerate() {
+            self.glyph_brush.queue(Section {
+                text,
+                color: *color,
+                screen_position: (self.width as f32 * 0.1, self.height as f32 * 0.1 + line_i as f32 * 50.0),
+                scale: GlyphScale::uniform(26.0),
+                .. Section::default()
+            });
+        }
+    }
+
     fn draw_player_result(&mut self, result: &PlayerResult, start_x: f32) {
         let fighter_name = self.package.as_ref().unwrap().fighters[result.fighter.as_ref()].name.as_ref();
         let color = graphics::get_team_color4(result.team);
@@ -1435,6 +1653,24 @@ impl WgpuGraphics {
             .. Section::default()
         });
         y += 30.0;
+        if let (Some(team_kills), Some(team_deaths)) = (result.team_kills, result.team_deaths) {
+            self.glyph_brush.queue(Section {
+                text: format!("Team Kills: {}", team_kills).as_str(),
+                color,
+                screen_position: (x, y),
+                scale: GlyphScale::uniform(30.0),
+                .. Section::default()
+            });
+            y += 30.0;
+            self.glyph_brush.queue(Section {
+                text: format!("Team Deaths: {}", team_deaths).as_str(),
+                color,
+                screen_position: (x, y),
+                scale: GlyphScale::uniform(30.0),
+                .. Section::default()
+            });
+            y += 30.0;
+        }
         self.glyph_brush.queue(Section {
             text: format!("L-Cancel Success: {}%", result.lcancel_percent).as_str(),
             color,
@@ -1473,6 +1709,12 @@ impl WgpuGraphics {
     }
 }
 
+/// e.g. "Best of 3: Blue 2 - Orange 1"
+fn set_score(set: &Set) -> String {
+    let scores: Vec<String> = set.teams.iter().map(|team| format!("{} {}", graphics::get_team_name(*team), set.wins(*team))).collect();
+    format!("Best of {}: {}", set.best_of, scores.join(" - "))
+}
+
 struct WindowSizeDependent {
     swap_chain:               Option<SwapChain>,
     multisampled_framebuffer: TextureView,
@@ -11,6 +11,8 @@ pub struct Config {
     pub current_package:       Option<String>,
     pub netplay_region:        Option<String>,
     pub auto_save_replay:      bool,
+    #[serde(default)]
+    pub input_only_replays:    bool, // saved replays are re-simulated from inputs when played back, much smaller but slower to rewind
     pub verify_package_hashes: bool,
     pub fullscreen:            bool,
     pub physical_device_name:  Option<String>,
@@ -50,6 +52,7 @@ impl Default for Config {
             current_package:       None,
             netplay_region:        None,
             auto_save_replay:      false,
+            input_only_replays:    false,
             verify_package_hashes: true,
             fullscreen:            false,
             physical_device_name:  None,
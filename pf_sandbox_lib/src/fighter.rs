@@ -18,7 +18,9 @@ impl Default for Fighter {
                 Action::DamageFall | Action::AerialDodge |
                 Action::Uair       | Action::Dair |
                 Action::Fair       | Action::Bair |
-                Action::Nair => false,
+                Action::Nair       | Action::NspecialAir |
+                Action::SspecialAir | Action::UspecialAir |
+                Action::DspecialAir => false,
                 _ => true
             };
             action_def_new.frames[0].ledge_cancel = match action {
@@ -36,6 +38,25 @@ impl Default for Fighter {
                   => true,
                 _ => false
             };
+            action_def_new.frames[0].ledge_grab_box = match action {
+                Action::UspecialGround | Action::UspecialAir
+                  => Some(LedgeGrabBox::default()),
+                _ => None
+            };
+            let grab_hitbox = match action {
+                Action::Pummel => Some(HitBox { damage: 3.0, bkb: 0.0,  kbg: 0.0, angle: 0.0,   enable_clang: false, enable_rebound: false, .. HitBox::default() }),
+                Action::ThrowF => Some(HitBox { damage: 8.0, bkb: 60.0, kbg: 0.5, angle: 45.0,  enable_clang: false, enable_rebound: false, .. HitBox::default() }),
+                Action::ThrowB => Some(HitBox { damage: 8.0, bkb: 60.0, kbg: 0.5, angle: 135.0, enable_clang: false, enable_rebound: false, .. HitBox::default() }),
+                Action::ThrowU => Some(HitBox { damage: 8.0, bkb: 60.0, kbg: 0.5, angle: 90.0,  enable_clang: false, enable_rebound: false, .. HitBox::default() }),
+                Action::ThrowD => Some(HitBox { damage: 8.0, bkb: 60.0, kbg: 0.5, angle: 80.0,  enable_clang: false, enable_rebound: false, .. HitBox::default() }),
+                _ => None
+            };
+            if let Some(hitbox) = grab_hitbox {
+                action_def_new.frames[0].colboxes.push(CollisionBox {
+                    role: CollisionBoxRole::Hit (hitbox),
+                    .. CollisionBox::default()
+                });
+            }
             actions.push(action_def_new);
         }
 
@@ -82,11 +103,15 @@ impl Default for Fighter {
             shield:                   None,
             power_shield:             None,
             tech:                     None,
+            wall_jump:                None,
             missed_tech_forced_getup: Some(200),
+            crouch_cancel_kb_mult:    Some(0.67),
+            ledge_intangibility:      30,
             run_turn_flip_dir_frame:  30,
             tilt_turn_flip_dir_frame: 5,
             tilt_turn_into_dash_iasa: 5,
             actions:                  actions,
+            projectiles:              ContextVec::new(),
         }
     }
 }
@@ -135,11 +160,15 @@ pub struct Fighter {
     pub shield:                   Option<Shield>,
     pub power_shield:             Option<PowerShield>,
     pub tech:                     Option<Tech>,
+    pub wall_jump:                Option<WallJump>,
     pub missed_tech_forced_getup: Option<u64>,
+    pub crouch_cancel_kb_mult:    Option<f32>, // knockback is multiplied by this when hit while crouching
+    pub ledge_intangibility:      u64, // frames of intangibility given by grabbing a ledge
     pub run_turn_flip_dir_frame:  u64,
     pub tilt_turn_flip_dir_frame: u64,
     pub tilt_turn_into_dash_iasa: u64,
     pub actions:                  ContextVec<ActionDef>,
+    pub projectiles:              ContextVec<ProjectileDef>,
 }
 
 #[derive(Clone, Serialize, Deserialize, Node)]
@@ -157,6 +186,23 @@ impl Default for Tech {
     }
 }
 
+#[derive(Clone, Serialize, Deserialize, Node)]
+pub struct WallJump {
+    pub x_vel: f32,
+    pub y_vel: f32,
+    pub range: f32, // maximum distance from the ECB to the wall
+}
+
+impl Default for WallJump {
+    fn default() -> Self {
+        WallJump {
+            x_vel: 1.3,
+            y_vel: 2.8,
+            range: 3.0,
+        }
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Node)]
 pub struct LCancel {
     pub active_window: u64,
@@ -224,6 +270,50 @@ pub struct ActionDef {
     pub iasa:   i64,
 }
 
+#[derive(Clone, Serialize, Deserialize, Node)]
+pub struct ProjectileDef {
+    pub name:           String,
+    pub frames:         ContextVec<ActionFrame>, // loops for the lifetime of the projectile
+    pub lifetime:       u64,
+    pub x_vel:          f32,
+    pub y_vel:          f32,
+    pub gravity:        f32,
+    pub destroy_on_hit: bool,
+    pub reflectable:    bool,
+    pub absorbable:     bool,
+}
+
+impl Default for ProjectileDef {
+    fn default() -> ProjectileDef {
+        let mut frame = ActionFrame::default();
+        frame.colboxes.push(CollisionBox {
+            role: CollisionBoxRole::Hit (HitBox::default()),
+            .. CollisionBox::default()
+        });
+
+        ProjectileDef {
+            name:           "Projectile".to_string(),
+            frames:         ContextVec::from_vec(vec!(frame)),
+            lifetime:       60,
+            x_vel:          2.0,
+            y_vel:          0.0,
+            gravity:        0.0,
+            destroy_on_hit: true,
+            reflectable:    true,
+            absorbable:     true,
+        }
+    }
+}
+
+/// Spawns the projectile at index `projectile` in `Fighter::projectiles`
+/// x and y are relative to the fighter and x is flipped when the fighter faces left.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize, Node)]
+pub struct ProjectileSpawn {
+    pub projectile: usize,
+    pub x:          f32,
+    pub y:          f32,
+}
+
 #[derive(Clone, Serialize, Deserialize, Node)]
 pub enum VelModify {
     Set (f32),
@@ -250,7 +340,7 @@ pub struct ActionFrame {
     pub pass_through:        bool, // only used on aerial actions
     pub ledge_cancel:        bool, // only used on ground actions
     pub use_platform_angle:  bool, // only used on ground actions
-    // TODO: pub land_cancel: bool // only used on aerial attacks
+    pub land_cancel:         bool, // only used on special actions, when false the action continues after landing
     pub ledge_grab_box:      Option<LedgeGrabBox>,
     pub force_hitlist_reset: bool,
     /// Affects the next frames velocity
@@ -261,6 +351,7 @@ pub struct ActionFrame {
     pub x_vel_temp: f32,
     /// Does not affect the next frames velocity
     pub y_vel_temp: f32,
+    pub projectile_spawns: Vec<ProjectileSpawn>,
 }
 
 impl Default for ActionFrame {
@@ -280,9 +371,11 @@ impl Default for ActionFrame {
             y_vel_temp:           0.0,
             pass_through:        true,
             ledge_cancel:        true,
+            land_cancel:         true,
             use_platform_angle:  false,
             ledge_grab_box:      None,
             force_hitlist_reset: false,
+            projectile_spawns:   vec!(),
         }
     }
 }
@@ -451,7 +544,7 @@ impl RenderOrder {
     }
 }
 
-#[derive(Clone, Serialize, Deserialize, Node)]
+#[derive(Clone, PartialEq, Serialize, Deserialize, Node)]
 pub struct ECB {
     pub left:   f32,
     pub right:  f32,
@@ -558,6 +651,7 @@ pub enum Action {
     LedgeJumpSlow,
     LedgeGetup,
     LedgeGetupSlow,
+    WallJump,
 
     // Defense
     PowerShield,
@@ -573,6 +667,8 @@ pub enum Action {
     TechF,
     TechN,
     TechB,
+    WallTech,
+    CeilingTech,
     MissedTechGetupF,
     MissedTechGetupN,
     MissedTechGetupB,
@@ -585,6 +681,8 @@ pub enum Action {
     ShieldBreakGetup,
     Stun,
     MissedTechStart,
+    Sleep,
+    Frozen,
 
     // Attacks
     Jab,
@@ -603,6 +701,17 @@ pub enum Action {
     LedgeAttackSlow,
     MissedTechAttack,
 
+    // Grabs
+    GrabbingIdle,
+    GrabbingEnd,
+    Pummel,
+    ThrowF,
+    ThrowB,
+    ThrowU,
+    ThrowD,
+    GrabbedIdle,
+    GrabbedEnd,
+
     // Aerials
     Uair,
     Dair,
@@ -615,6 +724,16 @@ pub enum Action {
     BairLand,
     NairLand,
 
+    // Specials
+    NspecialGround,
+    NspecialAir,
+    SspecialGround,
+    SspecialAir,
+    UspecialGround,
+    UspecialAir,
+    DspecialGround,
+    DspecialAir,
+
     // Taunts
     TauntUp,
     TauntDown,
@@ -656,6 +775,28 @@ impl Action {
         }
     }
 
+    pub fn is_special(&self) -> bool {
+        match self {
+            &Action::NspecialGround | &Action::NspecialAir |
+            &Action::SspecialGround | &Action::SspecialAir |
+            &Action::UspecialGround | &Action::UspecialAir |
+            &Action::DspecialGround | &Action::DspecialAir
+              => true,
+            _ => false
+        }
+    }
+
+    /// The player is holding a grabbed player during these actions
+    pub fn is_grabbing(&self) -> bool {
+        match self {
+            &Action::GrabbingIdle | &Action::Pummel |
+            &Action::ThrowF       | &Action::ThrowB |
+            &Action::ThrowU       | &Action::ThrowD
+              => true,
+            _ => false
+        }
+    }
+
     pub fn is_land(&self) -> bool {
         match self {
             &Action::FairLand | &Action::BairLand |
@@ -750,7 +891,7 @@ pub struct HitBox {
     pub enable_rebound:     bool,
     pub effect:             HitboxEffect,
     pub enable_reverse_hit: bool, // if the defender is behind the attacker the direction is reversed.
-    //pub team_funnel_angle: Option<f32>, // degrees to +- towards nearest teammate
+    pub team_funnel_angle:  Option<f32>, // degrees to +- towards nearest teammate
 }
 
 impl Default for HitBox {
@@ -766,6 +907,7 @@ impl Default for HitBox {
             enable_reverse_hit: true,
             hitstun:            HitStun::default(),
             effect:             HitboxEffect::default(),
+            team_funnel_angle:  None,
         }
     }
 }
@@ -785,11 +927,11 @@ impl Default for HitStun {
 #[derive(Debug, Clone, Serialize, Deserialize, Node)]
 pub enum HitboxEffect {
     Fire,
-    Electric,
-    Sleep,
-    Reverse,
-    Stun,
-    Freeze,
+    Electric { hitlag_mult: f32 },
+    Sleep    { frames: u64 }, // frames asleep before any mashing
+    Reverse,  // defender is turned away from the attacker
+    Stun     { frames: u64 },
+    Freeze   { frames: u64 },
     None,
 }
 
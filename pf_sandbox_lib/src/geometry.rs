@@ -41,6 +41,21 @@ fn point_on_segment(p: (f32, f32), q: (f32, f32), r: (f32, f32)) -> bool {
     q.1 <= p.1.max(r.1) && q.1 >= p.1.min(r.1)
 }
 
+/// Returns the shortest distance from point p to segment ab
+pub fn point_segment_distance(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let d_x = b.0 - a.0;
+    let d_y = b.1 - a.1;
+    let length_sq = d_x * d_x + d_y * d_y;
+    let t = if length_sq == 0.0 {
+        0.0
+    } else {
+        (((p.0 - a.0) * d_x + (p.1 - a.1) * d_y) / length_sq).max(0.0).min(1.0)
+    };
+    let c_x = a.0 + t * d_x;
+    let c_y = a.1 + t * d_y;
+    ((p.0 - c_x).powi(2) + (p.1 - c_y).powi(2)).sqrt()
+}
+
 #[derive(Clone, Default, Serialize, Deserialize, Node)]
 pub struct Rect {
     pub x1: f32,
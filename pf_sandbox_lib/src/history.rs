@@ -0,0 +1,204 @@
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+use treeflection::{Node, NodeRunner};
+
+/// Frames between each full copy of the values, seeking has to apply at most KEYFRAME_INTERVAL - 1 deltas
+pub const KEYFRAME_INTERVAL: usize = 60;
+
+/// A value that can be recorded as the fields that changed since the previous frame
+pub trait Delta: Clone + Serialize + DeserializeOwned {
+    type Delta: Clone + Serialize + DeserializeOwned;
+
+    /// A copy containing only the recorded fields, anything derived from them is reset
+    fn recorded(&self) -> Self;
+
+    /// The recorded fields of self that differ from previous
+    fn delta(&self, previous: &Self) -> Self::Delta;
+
+    fn apply(&mut self, delta: &Self::Delta);
+}
+
+/// The values on every frame of a game, the number of values must stay the same on every frame.
+/// Every KEYFRAME_INTERVAL frames the values are stored in full, the frames in between only store the changes since the previous frame.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct History<T: Delta> {
+    keyframes: Vec<Vec<T>>,
+    deltas:    Vec<Vec<T::Delta>>, // one entry per frame, empty on keyframes
+    #[serde(skip)]
+    last:      Option<(usize, Vec<T>)>, // the last frame decoded, so the most recent frame is never decoded from the keyframe
+}
+
+impl<T: Delta> Default for History<T> {
+    fn default() -> History<T> {
+        History {
+            keyframes: vec!(),
+            deltas:    vec!(),
+            last:      None,
+        }
+    }
+}
+
+impl<T: Delta> Node for History<T> {
+    fn node_step(&mut self, _: NodeRunner) -> String {
+        String::from("History is not accessible via treeflection.")
+    }
+}
+
+impl<T: Delta> History<T> {
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    /// Record the values for the next frame
+    pub fn push(&mut self, values: &[T]) {
+        let frame = self.deltas.len();
+        if frame % KEYFRAME_INTERVAL == 0 {
+            let keyframe: Vec<T> = values.iter().map(|x| x.recorded()).collect();
+            self.keyframes.push(keyframe.clone());
+            self.deltas.push(vec!());
+            self.last = Some((frame, keyframe));
+        }
+        else {
+            let mut previous = match self.last.take() {
+                Some((last_frame, previous)) if last_frame + 1 == frame => previous,
+                _ => self.get(frame - 1).unwrap()
+            };
+            let deltas: Vec<T::Delta> = values.iter().zip(previous.iter()).map(|(value, previous)| value.delta(previous)).collect();
+            for (previous, delta) in previous.iter_mut().zip(deltas.iter()) {
+                previous.apply(delta);
+            }
+            self.deltas.push(deltas);
+            self.last = Some((frame, previous));
+        }
+    }
+
+    pub fn get(&self, frame: usize) -> Option<Vec<T>> {
+        if frame >= self.deltas.len() {
+            return None;
+        }
+        if let Some((last_frame, last)) = &self.last {
+            if *last_frame == frame {
+                return Some(last.clone());
+            }
+        }
+
+        let keyframe_i = frame / KEYFRAME_INTERVAL;
+        let mut values = self.keyframes[keyframe_i].clone();
+        for deltas in &self.deltas[keyframe_i * KEYFRAME_INTERVAL + 1 ..= frame] {
+            for (value, delta) in values.iter_mut().zip(deltas) {
+                value.apply(delta);
+            }
+        }
+        Some(values)
+    }
+
+    /// The values on each frame in range, frames that were not recorded are skipped
+    pub fn range(&self, range: Range<usize>) -> Vec<Vec<T>> {
+        let mut frames = vec!();
+        self.for_each(range, |_, values| frames.push(values.to_vec()));
+        frames
+    }
+
+    /// Call f with the values on each frame in range, frames that were not recorded are skipped.
+    /// Each frame is decoded from the previous one, so this is much cheaper than calling get on each frame.
+    pub fn for_each<F>(&self, range: Range<usize>, mut f: F) where F: FnMut(usize, &[T]) {
+        let end = range.end.min(self.deltas.len());
+        if range.start >= end {
+            return;
+        }
+
+        let mut values = self.get(range.start).unwrap();
+        f(range.start, &values);
+        for frame in range.start + 1 .. end {
+            if frame % KEYFRAME_INTERVAL == 0 {
+                values = self.keyframes[frame / KEYFRAME_INTERVAL].clone();
+            } else {
+                for (value, delta) in values.iter_mut().zip(&self.deltas[frame]) {
+                    value.apply(delta);
+                }
+            }
+            f(frame, &values);
+        }
+    }
+
+    pub fn first(&self) -> Option<&Vec<T>> {
+        self.keyframes.first()
+    }
+
+    /// Remove all frames from len onwards
+    pub fn truncate(&mut self, len: usize) {
+        self.deltas.truncate(len);
+        self.keyframes.truncate((len + KEYFRAME_INTERVAL - 1) / KEYFRAME_INTERVAL);
+        if self.last.as_ref().map_or(false, |(frame, _)| *frame >= len) {
+            self.last = None;
+        }
+    }
+}
+
+/// The frames leading up to a frame kept decoded, so they can be used every frame without decoding the history
+#[derive(Clone)]
+pub struct RecentFrames<T> {
+    frames:   VecDeque<Vec<T>>,
+    end:      usize, // frames are the frames of history before end
+    capacity: usize,
+}
+
+impl<T> Default for RecentFrames<T> {
+    fn default() -> RecentFrames<T> {
+        RecentFrames::new(0)
+    }
+}
+
+impl<T> Node for RecentFrames<T> {
+    fn node_step(&mut self, _: NodeRunner) -> String {
+        String::from("RecentFrames is not accessible via treeflection.")
+    }
+}
+
+impl<T> RecentFrames<T> {
+    pub fn new(capacity: usize) -> RecentFrames<T> {
+        RecentFrames {
+            frames: VecDeque::with_capacity(capacity + 1),
+            end:    0,
+            capacity,
+        }
+    }
+
+    /// Oldest frame first
+    pub fn frames(&self) -> &VecDeque<Vec<T>> {
+        &self.frames
+    }
+
+    /// Forget frames from len onwards, they are decoded again on the next update as the history was rewritten
+    pub fn truncate(&mut self, len: usize) {
+        if self.end > len {
+            self.frames.clear();
+            self.end = 0;
+        }
+    }
+}
+
+impl<T: Delta> RecentFrames<T> {
+    /// Keep the frames of history before end, only the newest frame needs to be decoded when stepping forward one frame
+    pub fn update(&mut self, history: &History<T>, end: usize) {
+        let complete = self.frames.len() == self.end.min(self.capacity);
+        if complete && end == self.end + 1 && end <= history.len() {
+            self.frames.push_back(history.get(end - 1).unwrap());
+            if self.frames.len() > self.capacity {
+                self.frames.pop_front();
+            }
+        }
+        else if end != self.end {
+            self.frames = history.range(end.saturating_sub(self.capacity) .. end).into_iter().collect();
+        }
+        self.end = end;
+    }
+}
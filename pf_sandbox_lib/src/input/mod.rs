@@ -33,13 +33,8 @@ impl Deadzone {
         }
     }
 
-    pub fn empty4() -> [Self; 4] {
-        [
-            Deadzone::empty(),
-            Deadzone::empty(),
-            Deadzone::empty(),
-            Deadzone::empty()
-        ]
+    pub fn empty_vec(count: usize) -> Vec<Self> {
+        (0..count).map(|_| Deadzone::empty()).collect()
     }
 }
 
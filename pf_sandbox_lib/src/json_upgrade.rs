@@ -2,7 +2,7 @@ use serde_json::{Value, Number};
 
 pub fn build_version() -> String { String::from(env!("BUILD_VERSION")) }
 
-pub fn engine_version() -> u64 { 15 }
+pub fn engine_version() -> u64 { 33 }
 
 pub fn engine_version_json() -> Value {
     Value::Number(Number::from(engine_version()))
@@ -25,7 +25,7 @@ fn upgrade_engine_version(meta: &mut Value) {
     }
 }
 
-pub(crate) fn upgrade_to_latest_fighter(fighter: &mut Value, file_name: &str) {
+pub fn upgrade_to_latest_fighter(fighter: &mut Value, file_name: &str) {
     let fighter_engine_version = get_engine_version(fighter);
     if fighter_engine_version > engine_version() {
         println!("Fighter: {} is newer than this version of PF Sandbox. Please upgrade to the latest version.", file_name);
@@ -34,6 +34,14 @@ pub(crate) fn upgrade_to_latest_fighter(fighter: &mut Value, file_name: &str) {
     else if fighter_engine_version < engine_version() {
         for upgrade_from in fighter_engine_version..engine_version() {
             match upgrade_from {
+                27 => { upgrade_fighter27(fighter) }
+                24 => { upgrade_fighter24(fighter) }
+                20 => { upgrade_fighter20(fighter) }
+                19 => { upgrade_fighter19(fighter) }
+                18 => { upgrade_fighter18(fighter) }
+                17 => { upgrade_fighter17(fighter) }
+                16 => { upgrade_fighter16(fighter) }
+                15 => { upgrade_fighter15(fighter) }
                 14 => { upgrade_fighter14(fighter) }
                 13 => { upgrade_fighter13(fighter) }
                 12 => { upgrade_fighter12(fighter) }
@@ -63,19 +71,40 @@ pub(crate) fn upgrade_to_latest_stage(stage: &mut Value, file_name: &str) {
         // TODO: Display warning in window
     }
     else if stage_engine_version < engine_version() {
-        // TODO: Handle upgrades here
+        for upgrade_from in stage_engine_version..engine_version() {
+            match upgrade_from {
+                23 => { upgrade_stage23(stage) }
+                22 => { upgrade_stage22(stage) }
+                21 => { upgrade_stage21(stage) }
+                _ => { }
+            }
+        }
         upgrade_engine_version(stage);
     }
 }
 
-pub(crate) fn upgrade_to_latest_rules(rules: &mut Value) {
+pub fn upgrade_to_latest_rules(rules: &mut Value) {
     let rules_engine_version = get_engine_version(rules);
     if rules_engine_version > engine_version() {
         println!("rules.json is newer than this version of PF Sandbox. Please upgrade to the latest version.");
         // TODO: Display warning in window
     }
     else if rules_engine_version < engine_version() {
-        // TODO: Handle upgrades here
+        for upgrade_from in rules_engine_version..engine_version() {
+            match upgrade_from {
+                32 => { upgrade_rules32(rules) }
+                31 => { upgrade_rules31(rules) }
+                30 => { upgrade_rules30(rules) }
+                29 => { upgrade_rules29(rules) }
+                28 => { upgrade_rules28(rules) }
+                27 => { upgrade_rules27(rules) }
+                26 => { upgrade_rules26(rules) }
+                25 => { upgrade_rules25(rules) }
+                24 => { upgrade_rules24(rules) }
+                22 => { upgrade_rules22(rules) }
+                _ => { }
+            }
+        }
         upgrade_engine_version(rules);
     }
 }
@@ -106,6 +135,471 @@ fn get_vec<'a>(parent: &'a mut Value, member: &str) -> Option<&'a mut Vec<Value>
 // Important:
 // Upgrades cannot rely on current structs as future changes may break those past upgrades
 
+/// Add grab mash rules
+fn upgrade_rules32(rules: &mut Value) {
+    if let &mut Value::Object (ref mut rules) = rules {
+        rules.insert(String::from("grab_mash_base"), json!(90.0));
+        rules.insert(String::from("grab_mash_damage_mult"), json!(1.7));
+    }
+}
+
+/// Add sudden death rules
+fn upgrade_rules31(rules: &mut Value) {
+    if let &mut Value::Object (ref mut rules) = rules {
+        rules.insert(String::from("sudden_death"), Value::Null);
+    }
+}
+
+/// Add set stage rules
+fn upgrade_rules30(rules: &mut Value) {
+    if let &mut Value::Object (ref mut rules) = rules {
+        rules.insert(String::from("starter_stages"), json!([]));
+        rules.insert(String::from("counterpick_stages"), json!([]));
+        rules.insert(String::from("counterpick_bans"), json!(2));
+    }
+}
+
+/// Add stocks to Teams::On
+fn upgrade_rules29(rules: &mut Value) {
+    if let Some (&mut Value::Object (ref mut teams)) = rules.get_mut("teams") {
+        if let Some (&mut Value::Object (ref mut on)) = teams.get_mut("On") {
+            on.insert(String::from("stocks"), json!("Individual"));
+        }
+    }
+}
+
+/// Add kill attribution rules
+fn upgrade_rules28(rules: &mut Value) {
+    if let &mut Value::Object (ref mut rules) = rules {
+        rules.insert(String::from("kill_credit_frames"), json!(600));
+        rules.insert(String::from("self_destruct_penalty"), json!(1));
+    }
+}
+
+/// Add ledge rules, matching the previous behaviour
+fn upgrade_rules27(rules: &mut Value) {
+    if let &mut Value::Object (ref mut rules) = rules {
+        rules.insert(String::from("ledge_grab_limit"), Value::Null);
+        rules.insert(String::from("ledge_regrab_intangibility"), json!(true));
+        rules.insert(String::from("ledge_hog"), json!(true));
+    }
+}
+
+/// Add ledge_intangibility to fighter
+fn upgrade_fighter27(fighter: &mut Value) {
+    if let &mut Value::Object (ref mut fighter) = fighter {
+        fighter.insert(String::from("ledge_intangibility"), json!(0));
+    }
+}
+
+/// Add knockback to rules, matching the previously hardcoded formula
+fn upgrade_rules26(rules: &mut Value) {
+    if let &mut Value::Object (ref mut rules) = rules {
+        rules.insert(String::from("knockback"), json!({
+            "damage_mult":       0.05,
+            "percent_mult":      0.1,
+            "weight_mult":       1.4,
+            "base":              18.0,
+            "max":               2500.0,
+            "launch_vel_mult":   0.03,
+            "launch_dec":        0.051,
+            "hitstun_mult":      1.0,
+            "hitlag_damage_div": 3.0,
+            "hitlag_base":       3.0,
+            "rage":              null
+        }));
+    }
+}
+
+/// Add stale_moves to rules
+/// Stale moves are disabled to keep the behaviour of existing packages
+fn upgrade_rules25(rules: &mut Value) {
+    if let &mut Value::Object (ref mut rules) = rules {
+        rules.insert(String::from("stale_moves"), Value::Null);
+    }
+}
+
+/// Add smash DI and crouch cancel settings to rules
+/// Smash DI is disabled to keep the behaviour of existing packages
+fn upgrade_rules24(rules: &mut Value) {
+    if let &mut Value::Object (ref mut rules) = rules {
+        rules.insert(String::from("sdi_distance"), Value::Null);
+        rules.insert(String::from("asdi_distance"), Value::Null);
+        rules.insert(String::from("crouch_cancel"), json!(true));
+    }
+}
+
+/// Add crouch_cancel_kb_mult to fighter
+fn upgrade_fighter24(fighter: &mut Value) {
+    if let &mut Value::Object (ref mut fighter) = fighter {
+        fighter.insert(String::from("crouch_cancel_kb_mult"), json!(0.67));
+    }
+}
+
+/// Add conveyor and slippery to floors
+fn upgrade_stage23(stage: &mut Value) {
+    if let Some (surfaces) = get_vec(stage, "surfaces") {
+        for surface in surfaces {
+            if let &mut Value::Object (ref mut surface) = surface {
+                if let Some (&mut Value::Object (ref mut floor)) = surface.get_mut("floor") {
+                    floor.insert(String::from("conveyor"), json!(0.0));
+                    floor.insert(String::from("slippery"), json!(false));
+                }
+            }
+        }
+    }
+}
+
+/// Add hazards to rules
+fn upgrade_rules22(rules: &mut Value) {
+    if let &mut Value::Object (ref mut rules) = rules {
+        rules.insert(String::from("hazards"), json!(true));
+    }
+}
+
+/// Add hazards to stage
+fn upgrade_stage22(stage: &mut Value) {
+    if let &mut Value::Object (ref mut stage) = stage {
+        stage.insert(String::from("hazards"), json!([]));
+    }
+}
+
+/// Add motion to surfaces
+fn upgrade_stage21(stage: &mut Value) {
+    if let Some (surfaces) = get_vec(stage, "surfaces") {
+        for surface in surfaces {
+            if let &mut Value::Object (ref mut surface) = surface {
+                surface.insert(String::from("motion"), Value::Null);
+            }
+        }
+    }
+}
+
+/// Add wall_jump to fighter and add wall jump, wall tech and ceiling tech states
+fn upgrade_fighter20(fighter: &mut Value) {
+    if let Some (actions) = get_vec(fighter, "actions") {
+        // WallJump, WallTech, CeilingTech
+        for index in &[33, 47, 48] {
+            let action = json!({
+              "frames": [
+                {
+                  "ecb": {
+                    "top": 16.0,
+                    "left": -4.0,
+                    "right": 4.0,
+                    "bottom": 0.0
+                  },
+                  "colboxes": [],
+                  "colbox_links": [],
+                  "render_order": [],
+                  "item_hold_x": 4.0,
+                  "item_hold_y": 11.0,
+                  "grab_hold_x": 4.0,
+                  "grab_hold_y": 11.0,
+                  "pass_through": true,
+                  "ledge_cancel": true,
+                  "land_cancel": true,
+                  "use_platform_angle": false,
+                  "ledge_grab_box": null,
+                  "force_hitlist_reset": false,
+                  "x_vel_modify": "None",
+                  "y_vel_modify": "None",
+                  "x_vel_temp": 0.0,
+                  "y_vel_temp": 0.0,
+                  "projectile_spawns": []
+                }
+              ],
+              "iasa": 0
+            });
+            actions.insert(*index, action);
+        }
+    }
+
+    if let &mut Value::Object (ref mut fighter) = fighter {
+        fighter.insert(String::from("wall_jump"), Value::Null);
+    }
+}
+
+/// Add team_funnel_angle to HitBox
+fn upgrade_fighter19(fighter: &mut Value) {
+    fn upgrade_frames(frames: &mut Vec<Value>) {
+        for frame in frames {
+            if let Some (colboxes) = get_vec(frame, "colboxes") {
+                for colbox in colboxes {
+                    if let Some (&mut Value::Object (ref mut hitbox)) = colbox.get_mut("role").and_then(|x| x.get_mut("Hit")) {
+                        hitbox.insert(String::from("team_funnel_angle"), Value::Null);
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some (actions) = get_vec(fighter, "actions") {
+        for action in actions {
+            if let Some (frames) = get_vec(action, "frames") {
+                upgrade_frames(frames);
+            }
+        }
+    }
+
+    if let Some (projectiles) = get_vec(fighter, "projectiles") {
+        for projectile in projectiles {
+            if let Some (frames) = get_vec(projectile, "frames") {
+                upgrade_frames(frames);
+            }
+        }
+    }
+}
+
+/// Add parameters to HitboxEffect and add sleep and frozen states
+fn upgrade_fighter18(fighter: &mut Value) {
+    fn upgrade_frames(frames: &mut Vec<Value>) {
+        for frame in frames {
+            if let Some (colboxes) = get_vec(frame, "colboxes") {
+                for colbox in colboxes {
+                    if let Some (hitbox) = colbox.get_mut("role").and_then(|x| x.get_mut("Hit")) {
+                        if let &mut Value::Object (ref mut hitbox) = hitbox {
+                            let effect = match hitbox.get("effect").and_then(|x| x.as_str()) {
+                                Some ("Electric") => json!({ "Electric": { "hitlag_mult": 1.5 } }),
+                                Some ("Sleep")    => json!({ "Sleep":    { "frames": 120 } }),
+                                Some ("Stun")     => json!({ "Stun":     { "frames": 90 } }),
+                                Some ("Freeze")   => json!({ "Freeze":   { "frames": 90 } }),
+                                Some (effect)     => json!(effect),
+                                None              => json!("None"),
+                            };
+                            hitbox.insert(String::from("effect"), effect);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some (actions) = get_vec(fighter, "actions") {
+        for action in actions.iter_mut() {
+            if let Some (frames) = get_vec(action, "frames") {
+                upgrade_frames(frames);
+            }
+        }
+
+        for _ in 0..2 {
+            let action = json!({
+              "frames": [
+                {
+                  "ecb": {
+                    "top": 16.0,
+                    "left": -4.0,
+                    "right": 4.0,
+                    "bottom": 0.0
+                  },
+                  "colboxes": [],
+                  "colbox_links": [],
+                  "render_order": [],
+                  "item_hold_x": 4.0,
+                  "item_hold_y": 11.0,
+                  "grab_hold_x": 4.0,
+                  "grab_hold_y": 11.0,
+                  "pass_through": true,
+                  "ledge_cancel": true,
+                  "land_cancel": true,
+                  "use_platform_angle": false,
+                  "ledge_grab_box": null,
+                  "force_hitlist_reset": false,
+                  "x_vel_modify": "None",
+                  "y_vel_modify": "None",
+                  "x_vel_temp": 0.0,
+                  "y_vel_temp": 0.0,
+                  "projectile_spawns": []
+                }
+              ],
+              "iasa": 0
+            });
+            actions.insert(56, action);
+        }
+    }
+
+    if let Some (projectiles) = get_vec(fighter, "projectiles") {
+        for projectile in projectiles {
+            if let Some (frames) = get_vec(projectile, "frames") {
+                upgrade_frames(frames);
+            }
+        }
+    }
+}
+
+/// Add land_cancel to ActionFrame and add ground and air special states
+fn upgrade_fighter17(fighter: &mut Value) {
+    if let Some (actions) = get_vec(fighter, "actions") {
+        for action in actions {
+            if let Some (frames) = get_vec(action, "frames") {
+                for frame in frames {
+                    if let &mut Value::Object (ref mut frame) = frame {
+                        frame.insert(String::from("land_cancel"), json!(true));
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some (projectiles) = get_vec(fighter, "projectiles") {
+        for projectile in projectiles {
+            if let Some (frames) = get_vec(projectile, "frames") {
+                for frame in frames {
+                    if let &mut Value::Object (ref mut frame) = frame {
+                        frame.insert(String::from("land_cancel"), json!(true));
+                    }
+                }
+            }
+        }
+    }
+
+    let ledge_grab_box = json!({
+      "x1": 0.0,
+      "y1": 12.0,
+      "x2": 14.0,
+      "y2": 22.0
+    });
+
+    // NspecialGround, NspecialAir, SspecialGround, SspecialAir, UspecialGround, UspecialAir, DspecialGround, DspecialAir
+    let action_settings = vec!(
+        (true,  Value::Null),
+        (false, Value::Null),
+        (true,  Value::Null),
+        (false, Value::Null),
+        (true,  ledge_grab_box.clone()),
+        (false, ledge_grab_box),
+        (true,  Value::Null),
+        (false, Value::Null),
+    );
+
+    if let Some (actions) = get_vec(fighter, "actions") {
+        for (i, (pass_through, ledge_grab_box)) in action_settings.into_iter().enumerate() {
+            let action = json!({
+              "frames": [
+                {
+                  "ecb": {
+                    "top": 16.0,
+                    "left": -4.0,
+                    "right": 4.0,
+                    "bottom": 0.0
+                  },
+                  "colboxes": [],
+                  "colbox_links": [],
+                  "render_order": [],
+                  "item_hold_x": 4.0,
+                  "item_hold_y": 11.0,
+                  "grab_hold_x": 4.0,
+                  "grab_hold_y": 11.0,
+                  "pass_through": pass_through,
+                  "ledge_cancel": true,
+                  "land_cancel": true,
+                  "use_platform_angle": false,
+                  "ledge_grab_box": ledge_grab_box,
+                  "force_hitlist_reset": false,
+                  "x_vel_modify": "None",
+                  "y_vel_modify": "None",
+                  "x_vel_temp": 0.0,
+                  "y_vel_temp": 0.0,
+                  "projectile_spawns": []
+                }
+              ],
+              "iasa": 0
+            });
+            actions.insert(90 + i, action);
+        }
+    }
+}
+
+/// Add grab, pummel and throw states
+fn upgrade_fighter16(fighter: &mut Value) {
+    let hitbox = |damage: f32, bkb: f32, kbg: f32, angle: f32| json!({
+      "point": [0.0, 0.0],
+      "radius": 3.0,
+      "role": {
+        "Hit": {
+          "shield_damage": 0.0,
+          "damage": damage,
+          "bkb": bkb,
+          "kbg": kbg,
+          "angle": angle,
+          "hitstun": { "FramesTimesKnockback": 0.5 },
+          "enable_clang": false,
+          "enable_rebound": false,
+          "effect": "None",
+          "enable_reverse_hit": true
+        }
+      }
+    });
+
+    // GrabbingIdle, GrabbingEnd, Pummel, ThrowF, ThrowB, ThrowU, ThrowD, GrabbedIdle, GrabbedEnd
+    let action_colboxes = vec!(
+        json!([]),
+        json!([]),
+        json!([hitbox(3.0, 0.0,  0.0, 0.0)]),
+        json!([hitbox(8.0, 60.0, 0.5, 45.0)]),
+        json!([hitbox(8.0, 60.0, 0.5, 135.0)]),
+        json!([hitbox(8.0, 60.0, 0.5, 90.0)]),
+        json!([hitbox(8.0, 60.0, 0.5, 80.0)]),
+        json!([]),
+        json!([]),
+    );
+
+    if let Some (actions) = get_vec(fighter, "actions") {
+        for (i, colboxes) in action_colboxes.into_iter().enumerate() {
+            let action = json!({
+              "frames": [
+                {
+                  "ecb": {
+                    "top": 16.0,
+                    "left": -4.0,
+                    "right": 4.0,
+                    "bottom": 0.0
+                  },
+                  "colboxes": colboxes,
+                  "colbox_links": [],
+                  "render_order": [],
+                  "item_hold_x": 4.0,
+                  "item_hold_y": 11.0,
+                  "grab_hold_x": 4.0,
+                  "grab_hold_y": 11.0,
+                  "pass_through": true,
+                  "ledge_cancel": true,
+                  "use_platform_angle": false,
+                  "ledge_grab_box": null,
+                  "force_hitlist_reset": false,
+                  "x_vel_modify": "None",
+                  "y_vel_modify": "None",
+                  "x_vel_temp": 0.0,
+                  "y_vel_temp": 0.0,
+                  "projectile_spawns": []
+                }
+              ],
+              "iasa": 0
+            });
+            actions.insert(71 + i, action);
+        }
+    }
+}
+
+/// Add projectiles to fighter and projectile_spawns to ActionFrame
+fn upgrade_fighter15(fighter: &mut Value) {
+    if let Some (actions) = get_vec(fighter, "actions") {
+        for action in actions {
+            if let Some (frames) = get_vec(action, "frames") {
+                for frame in frames {
+                    if let &mut Value::Object (ref mut frame) = frame {
+                        frame.insert(String::from("projectile_spawns"), json!([]));
+                    }
+                }
+            }
+        }
+    }
+
+    if let &mut Value::Object (ref mut fighter) = fighter {
+        fighter.insert(String::from("projectiles"), json!([]));
+    }
+}
+
 /// move set_x_vel/set_y_vel to x_vel_modify/y_vel_modify and x_vel_temp/y_vel_temp
 fn upgrade_fighter14(fighter: &mut Value) {
     if let Some (actions) = get_vec(fighter, "actions") {
@@ -11,6 +11,7 @@ pub mod config;
 pub mod fighter;
 pub mod files;
 pub mod geometry;
+pub mod history;
 pub mod input;
 pub mod json_upgrade;
 pub mod logger;
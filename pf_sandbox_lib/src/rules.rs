@@ -4,30 +4,64 @@ use crate::json_upgrade::engine_version;
 
 #[derive(Clone, Serialize, Deserialize, Node)]
 pub struct Rules {
-    pub engine_version:     u64,
-    pub title:              String,
-    pub goal:               Goal,
-    pub stock_count:        Option<u64>,
-    pub time_limit_seconds: Option<u64>,
-    pub best_of:            u64,
-    pub pause:              Pause,
-    pub teams:              Teams,
-    pub grab_clang:         bool,
+    pub engine_version:             u64,
+    pub title:                      String,
+    pub goal:                       Goal,
+    pub stock_count:                Option<u64>,
+    pub time_limit_seconds:         Option<u64>,
+    pub best_of:                    u64,
+    pub starter_stages:             Vec<String>, // stages struck from for the first game of a set, when empty every stage is a starter
+    pub counterpick_stages:         Vec<String>, // stages that can also be picked after the first game of a set
+    pub counterpick_bans:           u64, // stages the winner of a game bans before the loser picks the next stage
+    pub pause:                      Pause,
+    pub teams:                      Teams,
+    pub grab_clang:                 bool,
+    pub hazards:                    bool, // disable for competitive play
+    pub sdi_distance:               Option<f32>, // distance moved by each stick flick during hitlag
+    pub asdi_distance:              Option<f32>, // distance moved by the stick held on the final frame of hitlag
+    pub crouch_cancel:              bool,
+    pub stale_moves:                Option<StaleMoves>,
+    pub knockback:                  KnockbackFormula,
+    pub ledge_grab_limit:           Option<u64>, // ledge grabs allowed before landing or being hit
+    pub ledge_regrab_intangibility: bool, // when false only the first ledge grab before landing or being hit is intangible
+    pub ledge_hog:                  bool, // when false a ledge can be grabbed by multiple players at once
+    pub kill_credit_frames:         Option<u64>, // frames after being hit that a death still counts as a kill for the attacker, None never expires
+    pub self_destruct_penalty:      u64, // KillDeathScore points lost for a self-destruct, on top of the point lost for the death
+    pub sudden_death:               Option<SuddenDeath>, // when None, ties at the time limit are broken by damage
+    pub grab_mash_base:             f32, // frames a grabbed player is held for before mashing
+    pub grab_mash_damage_mult:      f32, // frames added to grab_mash_base per point of damage on the grabbed player
     //pub force_user_settings: User,
 }
 
 impl Default for Rules {
     fn default() -> Self {
         Rules {
-            engine_version:     engine_version(),
-            title:              "Base Game Mode".to_string(),
-            goal:               Goal::default(),
-            stock_count:        Some(4),
-            time_limit_seconds: Some(480),
-            best_of:            1,
-            pause:              Pause::default(),
-            teams:              Teams::default(),
-            grab_clang:         false,
+            engine_version:             engine_version(),
+            title:                      "Base Game Mode".to_string(),
+            goal:                       Goal::default(),
+            stock_count:                Some(4),
+            time_limit_seconds:         Some(480),
+            best_of:                    1,
+            starter_stages:             vec!(),
+            counterpick_stages:         vec!(),
+            counterpick_bans:           2,
+            pause:                      Pause::default(),
+            teams:                      Teams::default(),
+            grab_clang:                 false,
+            hazards:                    true,
+            sdi_distance:               Some(6.0),
+            asdi_distance:              Some(3.0),
+            crouch_cancel:              true,
+            stale_moves:                Some(StaleMoves::default()),
+            knockback:                  KnockbackFormula::default(),
+            ledge_grab_limit:           None,
+            ledge_regrab_intangibility: false,
+            ledge_hog:                  true,
+            kill_credit_frames:         Some(600),
+            self_destruct_penalty:      1,
+            sudden_death:               None,
+            grab_mash_base:             90.0,
+            grab_mash_damage_mult:      1.7,
         }
     }
 }
@@ -38,10 +72,134 @@ impl Rules {
     }
 }
 
+/// Players tied for first when time runs out play another round with a single stock, everyone else is eliminated
+#[derive(Clone, Serialize, Deserialize, Node)]
+pub struct SuddenDeath {
+    pub damage:             f32, // tied players respawn with this damage, in Stamina it is capped to leave them with 1 hp
+    pub time_limit_seconds: Option<u64>, // when the round times out, ties are broken by damage
+}
+
+impl Default for SuddenDeath {
+    fn default() -> Self {
+        SuddenDeath {
+            damage:             300.0,
+            time_limit_seconds: Some(60),
+        }
+    }
+}
+
+impl SuddenDeath {
+    pub fn time_limit_frames(&self) -> Option<u64> {
+        self.time_limit_seconds.map(|x| x * 60)
+    }
+}
+
+/// Damage of a move is reduced by the scaling of each position in the queue the move occupies
+#[derive(Clone, Serialize, Deserialize, Node)]
+pub struct StaleMoves {
+    pub queue_length: u64,
+    pub scaling:      Vec<f32>, // newest first, positions without an entry have no effect
+}
+
+impl Default for StaleMoves {
+    fn default() -> Self {
+        StaleMoves {
+            queue_length: 9,
+            scaling:      vec!(0.09, 0.08, 0.07, 0.06, 0.05, 0.04, 0.03, 0.02, 0.01),
+        }
+    }
+}
+
+/// Coefficients used to calculate knockback, hitstun and hitlag
+#[derive(Clone, Serialize, Deserialize, Node)]
+pub struct KnockbackFormula {
+    pub damage_mult:       f32, // multiplies the hitbox damage by the victims damage
+    pub percent_mult:      f32, // multiplies the victims damage
+    pub weight_mult:       f32,
+    pub base:              f32, // added to the damage and weight term before knockback growth is applied
+    pub max:               f32,
+    pub launch_vel_mult:   f32, // converts knockback into launch velocity
+    pub launch_dec:        f32, // launch velocity lost every frame
+    pub hitstun_mult:      f32, // multiplies hitstun that scales with knockback
+    pub hitlag_damage_div: f32,
+    pub hitlag_base:       f32,
+    pub rage:              Option<Rage>,
+}
+
+impl Default for KnockbackFormula {
+    fn default() -> Self {
+        KnockbackFormula {
+            damage_mult:       0.05,
+            percent_mult:      0.1,
+            weight_mult:       1.4,
+            base:              18.0,
+            max:               2500.0,
+            launch_vel_mult:   0.03,
+            launch_dec:        0.051,
+            hitstun_mult:      1.0,
+            hitlag_damage_div: 3.0,
+            hitlag_base:       3.0,
+            rage:              None,
+        }
+    }
+}
+
+impl KnockbackFormula {
+    /// victim_damage must include damage_done
+    pub fn knockback(&self, hitbox_damage: f32, damage_done: f32, victim_damage: f32, weight: f32, bkb: f32, kbg: f32) -> f32 {
+        let damage_launch = self.damage_mult * (hitbox_damage * (damage_done + victim_damage.floor())) + (damage_done + victim_damage) * self.percent_mult;
+        let weight = 2.0 - (2.0 * weight) / (1.0 + weight);
+        let rage = self.rage.as_ref().map_or(1.0, |rage| rage.mult(victim_damage));
+        ((bkb + kbg * (damage_launch * weight * self.weight_mult + self.base)) * rage).min(self.max)
+    }
+
+    pub fn hitlag(&self, hitbox_damage: f32) -> f32 {
+        hitbox_damage / self.hitlag_damage_div + self.hitlag_base
+    }
+}
+
+/// Increases knockback taken as the victims damage rises
+#[derive(Clone, Serialize, Deserialize, Node)]
+pub struct Rage {
+    pub min_damage: f32, // no effect below this damage
+    pub max_damage: f32, // max_mult is reached at this damage
+    pub max_mult:   f32,
+}
+
+impl Default for Rage {
+    fn default() -> Self {
+        Rage {
+            min_damage: 35.0,
+            max_damage: 150.0,
+            max_mult:   1.15,
+        }
+    }
+}
+
+impl Rage {
+    pub fn mult(&self, damage: f32) -> f32 {
+        let range = (self.max_damage - self.min_damage).max(0.001);
+        let progress = ((damage - self.min_damage) / range).max(0.0).min(1.0);
+        1.0 + (self.max_mult - 1.0) * progress
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Node)]
 pub enum Goal {
     KillDeathScore,
     LastManStanding,
+    /// Players lose a stock when damage depletes their hp, without stocks they only have a single life.
+    /// Knockback is calculated as if the victim had knockback_damage, so launches stay the same as hp drops.
+    Stamina { hp: f32, blast_zone_kills: bool, knockback_damage: f32 },
+}
+
+impl Goal {
+    pub fn stamina_hp(&self) -> Option<f32> {
+        match self {
+            &Goal::Stamina { hp, .. } => Some(hp),
+            _                         => None
+        }
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Node)]
@@ -53,10 +211,18 @@ pub enum Pause {
 
 #[derive(Clone, Serialize, Deserialize, Node)]
 pub enum Teams {
-    On { friendly_fire: bool },
+    On { friendly_fire: bool, stocks: TeamStocks },
     Off,
 }
 
+/// How stocks are shared between teammates in LastManStanding
+#[derive(Clone, Serialize, Deserialize, Node)]
+pub enum TeamStocks {
+    Individual,
+    Shared, // a player who loses their last stock automatically takes one from the teammate with the most stocks
+    Steal,  // an eliminated player can press A to take a stock from the teammate with the most stocks
+}
+
 impl Default for Goal {
     fn default() -> Self {
         Goal::LastManStanding
@@ -74,3 +240,9 @@ impl Default for Teams {
         Teams::Off
     }
 }
+
+impl Default for TeamStocks {
+    fn default() -> Self {
+        TeamStocks::Individual
+    }
+}
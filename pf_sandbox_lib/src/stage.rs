@@ -1,10 +1,14 @@
+use crate::fighter::HitBox;
 use crate::geometry::Rect;
+use crate::geometry;
 use winit_input_helper::WinitInputHelper;
 use crate::json_upgrade::engine_version;
 
 use treeflection::{Node, NodeRunner, NodeToken, ContextVec};
 use winit::event::VirtualKeyCode;
 
+use std::borrow::Cow;
+
 #[derive(Clone, Serialize, Deserialize, Node)]
 pub struct Stage {
     pub engine_version: u64,
@@ -14,6 +18,7 @@ pub struct Stage {
     pub camera:         Rect,
     pub spawn_points:   ContextVec<SpawnPoint>,
     pub respawn_points: ContextVec<SpawnPoint>,
+    pub hazards:        ContextVec<Hazard>,
 }
 
 impl Default for Stage {
@@ -29,8 +34,11 @@ impl Default for Stage {
             ceiling: false,
             floor: Some(Floor {
                 traction:     1.0,
-                pass_through: false
-            })
+                pass_through: false,
+                conveyor:     0.0,
+                slippery:     false,
+            }),
+            motion:  None,
         };
 
         let second_platform = Surface {
@@ -45,7 +53,10 @@ impl Default for Stage {
             floor: Some(Floor {
                 traction:     1.0,
                 pass_through: true,
-            })
+                conveyor:     0.0,
+                slippery:     false,
+            }),
+            motion:  None,
         };
 
         let blast = Rect {
@@ -116,38 +127,108 @@ impl Default for Stage {
             camera:         camera,
             spawn_points:   spawn_points,
             respawn_points: respawn_points,
+            hazards:        ContextVec::new(),
         }
     }
 }
 
 impl Stage {
-    /// return indexes to the floors connected to the passed floor
-    pub fn connected_floors(&self, platform_i: usize) -> FloorInfo {
-        let mut left_i = None;
-        let mut right_i = None;
-        if let Some(plat) = self.surfaces.get(platform_i) {
-            let (l_x, l_y) = plat.left_ledge();
-            let (r_x, r_y) = plat.right_ledge();
-            for (check_i, check_plat) in self.surfaces.iter().enumerate() {
-                if platform_i != check_i && check_plat.floor.is_some() {
-                    let (check_l_x, check_l_y) = check_plat.left_ledge();
-                    let (check_r_x, check_r_y) = check_plat.right_ledge();
-
-                    if l_x == check_r_x && l_y == check_r_y {
-                        left_i = Some(check_i);
-                    }
-                    if r_x == check_l_x && r_y == check_l_y {
-                        right_i = Some(check_i);
-                    }
+    /// returns the surfaces as they are positioned on the passed frame
+    /// Depends only on the frame number so that replays and netplay stay deterministic
+    /// Stages without any motion are borrowed as is.
+    pub fn surfaces_at(&self, frame: usize) -> Cow<[Surface]> {
+        if self.surfaces.iter().any(|x| x.motion.is_some()) {
+            Cow::Owned(self.surfaces.iter().map(|x| x.at_frame(frame)).collect())
+        } else {
+            let surfaces: &[Surface] = &self.surfaces;
+            Cow::Borrowed(surfaces)
+        }
+    }
+
+    /// returns the surfaces that are visible on the passed frame, positioned as they are on that frame
+    /// The indexes do not match the stage surfaces, so this is only suitable for rendering.
+    pub fn visible_surfaces_at(&self, frame: usize) -> Vec<Surface> {
+        self.surfaces.iter().filter(|x| x.visible_at(frame)).map(|x| x.at_frame(frame)).collect()
+    }
+
+    /// removes the surface and its hazards, the remaining hazards stay attached to the same surfaces
+    pub fn delete_surface(&mut self, surface_i: usize) {
+        self.surfaces.remove(surface_i);
+        let hazards: Vec<Hazard> = self.hazards.iter().cloned().filter_map(|mut hazard| {
+            if let HazardArea::Surface { surface_i: ref mut hazard_surface_i, .. } = hazard.area {
+                if *hazard_surface_i == surface_i {
+                    return None;
+                }
+                if *hazard_surface_i > surface_i {
+                    *hazard_surface_i -= 1;
                 }
             }
-        }
+            Some(hazard)
+        }).collect();
+        self.hazards = ContextVec::from_vec(hazards);
+    }
 
-        FloorInfo {
-            left_i,
-            right_i,
+    /// returns the spawn point used by the passed player at the start of a match
+    pub fn spawn_point(&self, player_i: usize) -> Option<SpawnPoint> {
+        cycle_spawn_points(&self.spawn_points, player_i)
+    }
+
+    /// returns the spawn point used by the passed player after dying
+    pub fn respawn_point(&self, player_i: usize) -> Option<SpawnPoint> {
+        cycle_spawn_points(&self.respawn_points, player_i)
+    }
+}
+
+/// Cycles through the points when there are more players than points.
+/// Each extra lap places the player between its point and the next point, so players never share a spawn unless the stage only has one.
+/// Players stay on their points side of the halfway mark, otherwise the two points of a stage would place their laps at the same midpoint.
+fn cycle_spawn_points(points: &ContextVec<SpawnPoint>, player_i: usize) -> Option<SpawnPoint> {
+    if points.len() == 0 {
+        return None;
+    }
+
+    let lap = player_i / points.len();
+    let point_i = player_i % points.len();
+    let point = &points[point_i];
+    if lap == 0 {
+        Some(point.clone())
+    } else {
+        let next = &points[(point_i + 1) % points.len()];
+        let t = lap as f32 / (lap + 1) as f32 / 2.0;
+        Some(SpawnPoint {
+            x:          point.x + (next.x - point.x) * t,
+            y:          point.y + (next.y - point.y) * t,
+            face_right: point.face_right,
+        })
+    }
+}
+
+/// return indexes to the floors connected to the passed floor
+pub fn connected_floors(surfaces: &[Surface], platform_i: usize) -> FloorInfo {
+    let mut left_i = None;
+    let mut right_i = None;
+    if let Some(plat) = surfaces.get(platform_i) {
+        let (l_x, l_y) = plat.left_ledge();
+        let (r_x, r_y) = plat.right_ledge();
+        for (check_i, check_plat) in surfaces.iter().enumerate() {
+            if platform_i != check_i && check_plat.floor.is_some() {
+                let (check_l_x, check_l_y) = check_plat.left_ledge();
+                let (check_r_x, check_r_y) = check_plat.right_ledge();
+
+                if l_x == check_r_x && l_y == check_r_y {
+                    left_i = Some(check_i);
+                }
+                if r_x == check_l_x && r_y == check_l_y {
+                    right_i = Some(check_i);
+                }
+            }
         }
     }
+
+    FloorInfo {
+        left_i,
+        right_i,
+    }
 }
 
 pub struct FloorInfo {
@@ -166,6 +247,7 @@ pub struct Surface {
     pub wall:    bool,
     pub ceiling: bool,
     pub floor:   Option<Floor>,
+    pub motion:  Option<SurfaceMotion>,
 }
 
 // TODO: coloring
@@ -177,6 +259,8 @@ pub struct Surface {
 pub struct Floor {
     pub traction:     f32,
     pub pass_through: bool,
+    pub conveyor:     f32, // velocity along the floor given to grounded players
+    pub slippery:     bool, // reduces friction and lets every action slide
 }
 
 impl Default for Floor {
@@ -184,7 +268,68 @@ impl Default for Floor {
         Floor {
             traction:     1.0,
             pass_through: true,
+            conveyor:     0.0,
+            slippery:     false,
+        }
+    }
+}
+
+/// Moves a surface relative to its rest position.
+/// Everything is calculated from the frame number so it is always deterministic.
+#[derive(Clone, Default, Serialize, Deserialize, Node)]
+pub struct SurfaceMotion {
+    pub path:           ContextVec<PathPoint>, // offsets from the rest position, the surface loops through them in order
+    pub rotation_speed: f32, // radians per frame around the centre of the surface
+    pub visible_frames: u64,
+    pub hidden_frames:  u64, // when 0 the surface is always visible
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, Node)]
+pub struct PathPoint {
+    pub x:      f32,
+    pub y:      f32,
+    pub frames: u64, // frames taken to travel to the next point
+}
+
+impl PathPoint {
+    pub fn new(x: f32, y: f32) -> PathPoint {
+        PathPoint {
+            x,
+            y,
+            frames: 60,
+        }
+    }
+}
+
+impl SurfaceMotion {
+    /// returns the offset from the rest position on the passed frame
+    pub fn offset(&self, frame: usize) -> (f32, f32) {
+        let loop_frames: u64 = self.path.iter().map(|x| x.frames.max(1)).sum();
+        if loop_frames == 0 {
+            return (0.0, 0.0);
         }
+
+        let mut frame = frame as u64 % loop_frames;
+        for (i, point) in self.path.iter().enumerate() {
+            let frames = point.frames.max(1);
+            if frame < frames {
+                let next = &self.path[(i + 1) % self.path.len()];
+                let t = frame as f32 / frames as f32;
+                return (point.x + (next.x - point.x) * t, point.y + (next.y - point.y) * t);
+            }
+            frame -= frames;
+        }
+        unreachable!();
+    }
+
+    /// returns the rotation from the rest angle on the passed frame
+    pub fn angle(&self, frame: usize) -> f32 {
+        // wrap in f64 so precision doesnt degrade over long games
+        (self.rotation_speed as f64 * frame as f64 % (2.0 * std::f64::consts::PI)) as f32
+    }
+
+    pub fn visible(&self, frame: usize) -> bool {
+        self.hidden_frames == 0 || frame as u64 % (self.visible_frames + self.hidden_frames) < self.visible_frames
     }
 }
 
@@ -193,10 +338,7 @@ impl Default for Floor {
 impl Surface {
     pub fn new(x1: f32, y1: f32, x2: f32, y2: f32, floor: bool, wall: bool, ceiling: bool) -> Surface {
         let floor = if floor {
-            Some(Floor {
-                traction:     1.0,
-                pass_through: true
-            })
+            Some(Floor::default())
         } else {
             None
         };
@@ -209,11 +351,58 @@ impl Surface {
             wall,
             ceiling,
             floor,
-            grab1: false,
-            grab2: false,
+            grab1:  false,
+            grab2:  false,
+            motion: None,
         }
     }
 
+    /// returns a copy of the surface positioned as it is on the passed frame
+    /// A hidden surface keeps its index but loses all of its collision
+    pub fn at_frame(&self, frame: usize) -> Surface {
+        let mut surface = self.clone();
+        if let Some(ref motion) = self.motion {
+            let (o_x, o_y) = motion.offset(frame);
+            let angle = motion.angle(frame);
+            if angle == 0.0 {
+                // translate the points directly so that connected surfaces stay exactly connected
+                surface.x1 += o_x;
+                surface.y1 += o_y;
+                surface.x2 += o_x;
+                surface.y2 += o_y;
+            } else {
+                let c_x = (self.x1 + self.x2) / 2.0 + o_x;
+                let c_y = (self.y1 + self.y2) / 2.0 + o_y;
+                let h_x = (self.x1 - self.x2) / 2.0;
+                let h_y = (self.y1 - self.y2) / 2.0;
+                let (sin, cos) = angle.sin_cos();
+                let r_x = h_x * cos - h_y * sin;
+                let r_y = h_x * sin + h_y * cos;
+                surface.x1 = c_x + r_x;
+                surface.y1 = c_y + r_y;
+                surface.x2 = c_x - r_x;
+                surface.y2 = c_y - r_y;
+            }
+
+            if !motion.visible(frame) {
+                surface.floor   = None;
+                surface.wall    = false;
+                surface.ceiling = false;
+                surface.grab1   = false;
+                surface.grab2   = false;
+            }
+        }
+        surface
+    }
+
+    pub fn visible_at(&self, frame: usize) -> bool {
+        self.motion.as_ref().map_or(true, |x| x.visible(frame))
+    }
+
+    pub fn center(&self) -> (f32, f32) {
+        ((self.x1 + self.x2) / 2.0, (self.y1 + self.y2) / 2.0)
+    }
+
     pub fn is_pass_through(&self) -> bool {
         if let &Some(ref floor) = &self.floor {
             floor.pass_through
@@ -357,12 +546,75 @@ impl SpawnPoint {
     }
 }
 
+/// An area of the stage that hits players with a hitbox and/or pushes them around
+#[derive(Clone, Default, Serialize, Deserialize, Node)]
+pub struct Hazard {
+    pub area:            HazardArea,
+    pub hitbox:          Option<HitBox>, // the angle is in world space, there is no attacker to face
+    pub wind_x:          f32, // added to the velocity of players in the area every frame
+    pub wind_y:          f32,
+    pub rehit_frames:    u64, // frames before a player can be hit by a hazard again
+    pub active_frames:   u64,
+    pub inactive_frames: u64, // when 0 the hazard is always active
+}
+
+#[derive(Clone, Serialize, Deserialize, Node)]
+pub enum HazardArea {
+    Rect (Rect),
+    Surface { surface_i: usize, range: f32 }, // follows the surface as it moves and disappears with it
+}
+
+impl Default for HazardArea {
+    fn default() -> HazardArea {
+        HazardArea::Rect (Rect::default())
+    }
+}
+
+impl Hazard {
+    pub fn active(&self, frame: usize) -> bool {
+        self.inactive_frames == 0 || frame as u64 % (self.active_frames + self.inactive_frames) < self.active_frames
+    }
+
+    /// returns true if the point is within the hazard
+    /// surfaces must be positioned for the frame being checked
+    pub fn contains_point(&self, surfaces: &[Surface], x: f32, y: f32) -> bool {
+        match &self.area {
+            &HazardArea::Rect (ref rect) => rect.contains_point(x, y),
+            &HazardArea::Surface { surface_i, range } => {
+                if let Some(surface) = surfaces.get(surface_i) {
+                    let solid = surface.floor.is_some() || surface.wall || surface.ceiling;
+                    solid && geometry::point_segment_distance((x, y), surface.p1(), surface.p2()) < range
+                } else {
+                    false
+                }
+            }
+        }
+    }
+
+    /// returns the area covered by the hazard, used for rendering
+    pub fn bounds(&self, surfaces: &[Surface]) -> Option<Rect> {
+        match &self.area {
+            &HazardArea::Rect (ref rect) => Some(rect.clone()),
+            &HazardArea::Surface { surface_i, range } => {
+                surfaces.get(surface_i).map(|surface| Rect {
+                    x1: surface.x1.min(surface.x2) - range,
+                    y1: surface.y1.min(surface.y2) - range,
+                    x2: surface.x1.max(surface.x2) + range,
+                    y2: surface.y1.max(surface.y2) + range,
+                })
+            }
+        }
+    }
+}
+
 #[derive(Clone, Default, Serialize, Deserialize, Node)]
 pub struct DebugStage {
     pub blast:          bool,
     pub camera:         bool,
     pub spawn_points:   bool,
     pub respawn_points: bool,
+    pub surface_paths:  bool,
+    pub hazards:        bool,
 }
 
 impl DebugStage {
@@ -379,12 +631,20 @@ impl DebugStage {
         if os_input.key_pressed(VirtualKeyCode::F4) {
             self.respawn_points = !self.respawn_points;
         }
+        if os_input.key_pressed(VirtualKeyCode::F5) {
+            self.surface_paths = !self.surface_paths;
+        }
+        if os_input.key_pressed(VirtualKeyCode::F6) {
+            self.hazards = !self.hazards;
+        }
         if os_input.key_pressed(VirtualKeyCode::F11) {
             *self = DebugStage {
                 blast:          true,
                 camera:         true,
                 spawn_points:   true,
                 respawn_points: true,
+                surface_paths:  true,
+                hazards:        true,
             }
         }
         if os_input.key_pressed(VirtualKeyCode::F12) {
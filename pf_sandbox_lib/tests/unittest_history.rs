@@ -0,0 +1,168 @@
+#[macro_use] extern crate serde_derive;
+
+use pf_sandbox_lib::history::{Delta, History, RecentFrames, KEYFRAME_INTERVAL};
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+struct Value {
+    a:       u64,
+    b:       u64,
+    derived: u64, // not recorded
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct ValueDelta {
+    a: Option<u64>,
+    b: Option<u64>,
+}
+
+impl Delta for Value {
+    type Delta = ValueDelta;
+
+    fn recorded(&self) -> Value {
+        Value { a: self.a, b: self.b, derived: 0 }
+    }
+
+    fn delta(&self, previous: &Value) -> ValueDelta {
+        ValueDelta {
+            a: if self.a != previous.a { Some(self.a) } else { None },
+            b: if self.b != previous.b { Some(self.b) } else { None },
+        }
+    }
+
+    fn apply(&mut self, delta: &ValueDelta) {
+        if let Some(a) = delta.a {
+            self.a = a;
+        }
+        if let Some(b) = delta.b {
+            self.b = b;
+        }
+    }
+}
+
+/// a changes every frame, b only changes every 7 frames
+fn frame(frame: usize) -> Vec<Value> {
+    vec!(
+        Value { a: frame as u64,       b: (frame / 7) as u64, derived: 1 },
+        Value { a: frame as u64 * 100, b: 3,                  derived: 2 },
+    )
+}
+
+fn recorded(frame_i: usize) -> Vec<Value> {
+    frame(frame_i).iter().map(|x| x.recorded()).collect()
+}
+
+fn history(len: usize) -> History<Value> {
+    let mut history = History::default();
+    for i in 0..len {
+        history.push(&frame(i));
+    }
+    history
+}
+
+#[test]
+fn history_get() {
+    let len = KEYFRAME_INTERVAL * 2 + 5;
+    let history = history(len);
+    assert_eq!(history.len(), len);
+
+    for i in 0..len {
+        assert_eq!(history.get(i), Some(recorded(i)), "frame {}", i);
+    }
+    assert_eq!(history.get(len), None);
+    assert_eq!(history.first(), Some(&recorded(0)));
+}
+
+#[test]
+fn history_range() {
+    let history = history(KEYFRAME_INTERVAL * 2 + 5);
+
+    let start = KEYFRAME_INTERVAL - 3;
+    let range = history.range(start .. KEYFRAME_INTERVAL * 2 + 2);
+    assert_eq!(range.len(), KEYFRAME_INTERVAL + 5);
+    for (i, values) in range.iter().enumerate() {
+        assert_eq!(values, &recorded(start + i), "frame {}", start + i);
+    }
+
+    // frames past the end are skipped
+    assert_eq!(history.range(KEYFRAME_INTERVAL * 2 .. KEYFRAME_INTERVAL * 3).len(), 5);
+    assert!(history.range(KEYFRAME_INTERVAL * 3 .. KEYFRAME_INTERVAL * 4).is_empty());
+}
+
+#[test]
+fn history_truncate() {
+    for &len in &[0, 1, KEYFRAME_INTERVAL - 1, KEYFRAME_INTERVAL, KEYFRAME_INTERVAL + 1, KEYFRAME_INTERVAL * 2] {
+        let mut history = history(KEYFRAME_INTERVAL * 2 + 5);
+        history.truncate(len);
+        assert_eq!(history.len(), len);
+        assert_eq!(history.get(len), None);
+
+        // the frames pushed after truncating replace the removed frames
+        for i in len .. KEYFRAME_INTERVAL * 3 {
+            let mut values = frame(i);
+            values[1].b = 4;
+            history.push(&values);
+        }
+        for i in 0 .. KEYFRAME_INTERVAL * 3 {
+            let mut expected = recorded(i);
+            if i >= len {
+                expected[1].b = 4;
+            }
+            assert_eq!(history.get(i), Some(expected), "truncated to {} frame {}", len, i);
+        }
+    }
+}
+
+#[test]
+fn history_serde() {
+    let history = history(KEYFRAME_INTERVAL + 5);
+    let json = serde_json::to_string(&history).unwrap();
+    let mut history: History<Value> = serde_json::from_str(&json).unwrap();
+
+    history.push(&frame(KEYFRAME_INTERVAL + 5));
+    for i in 0 .. KEYFRAME_INTERVAL + 6 {
+        assert_eq!(history.get(i), Some(recorded(i)), "frame {}", i);
+    }
+}
+
+#[test]
+fn recent_frames() {
+    let history = history(KEYFRAME_INTERVAL * 2);
+    let mut recent = RecentFrames::new(10);
+
+    // stepping forward
+    for end in 0..25 {
+        recent.update(&history, end);
+        let expected: Vec<_> = (end.saturating_sub(10) .. end).map(recorded).collect();
+        assert_eq!(recent.frames().iter().cloned().collect::<Vec<_>>(), expected, "end {}", end);
+    }
+
+    // jumping
+    for &end in &[KEYFRAME_INTERVAL + 3, 5, 4, KEYFRAME_INTERVAL * 2] {
+        recent.update(&history, end);
+        let expected: Vec<_> = (end.saturating_sub(10) .. end).map(recorded).collect();
+        assert_eq!(recent.frames().iter().cloned().collect::<Vec<_>>(), expected, "end {}", end);
+    }
+}
+
+#[test]
+fn recent_frames_truncate() {
+    let mut history = history(20);
+    let mut recent = RecentFrames::new(10);
+    recent.update(&history, 20);
+
+    // rewrite the history from frame 15
+    history.truncate(15);
+    recent.truncate(15);
+    let mut rewritten = vec!();
+    for i in 15..21 {
+        let mut values = frame(i);
+        values[0].b = 9;
+        history.push(&values);
+        rewritten.push(values.iter().map(|x| x.recorded()).collect::<Vec<_>>());
+    }
+
+    recent.update(&history, 21);
+    let mut expected: Vec<_> = (11..15).map(recorded).collect();
+    expected.extend(rewritten);
+    assert_eq!(recent.frames().iter().cloned().collect::<Vec<_>>(), expected);
+}
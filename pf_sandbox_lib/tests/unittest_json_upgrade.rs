@@ -0,0 +1,148 @@
+#[macro_use] extern crate serde_json;
+
+use pf_sandbox_lib::fighter::Action;
+use pf_sandbox_lib::json_upgrade::{engine_version, upgrade_to_latest_fighter, upgrade_to_latest_rules};
+use pf_sandbox_lib::rules::{Rules, Teams, TeamStocks};
+use serde_json::Value;
+use strum::IntoEnumIterator;
+
+/// The current rules with every field added by the rules upgrades removed
+fn rules21() -> Value {
+    let mut rules = serde_json::to_value(Rules::default()).unwrap();
+    if let Value::Object (ref mut rules) = rules {
+        let added = [
+            "hazards", "sdi_distance", "asdi_distance", "crouch_cancel", "stale_moves", "knockback",
+            "ledge_grab_limit", "ledge_regrab_intangibility", "ledge_hog", "kill_credit_frames", "self_destruct_penalty",
+            "starter_stages", "counterpick_stages", "counterpick_bans", "sudden_death", "grab_mash_base", "grab_mash_damage_mult",
+        ];
+        for field in added.iter() {
+            rules.remove(*field);
+        }
+        rules.insert(String::from("engine_version"), json!(21));
+        rules.insert(String::from("teams"), json!({ "On": { "friendly_fire": false } }));
+    }
+    rules
+}
+
+#[test]
+fn upgrade_rules() {
+    let mut json = rules21();
+    upgrade_to_latest_rules(&mut json);
+    let rules: Rules = serde_json::from_value(json).unwrap();
+
+    assert_eq!(rules.engine_version, engine_version());
+    assert!(rules.hazards);
+    assert!(rules.sdi_distance.is_none());
+    assert!(rules.asdi_distance.is_none());
+    assert!(rules.crouch_cancel);
+    assert!(rules.stale_moves.is_none());
+    assert_eq!(rules.knockback.base, 18.0);
+    assert_eq!(rules.knockback.max, 2500.0);
+    assert!(rules.knockback.rage.is_none());
+    assert!(rules.ledge_grab_limit.is_none());
+    assert!(rules.ledge_regrab_intangibility);
+    assert!(rules.ledge_hog);
+    assert_eq!(rules.kill_credit_frames, Some(600));
+    assert_eq!(rules.self_destruct_penalty, 1);
+    assert!(rules.starter_stages.is_empty());
+    assert!(rules.counterpick_stages.is_empty());
+    assert_eq!(rules.counterpick_bans, 2);
+    assert!(rules.sudden_death.is_none());
+    assert_eq!(rules.grab_mash_base, 90.0);
+    assert_eq!(rules.grab_mash_damage_mult, 1.7);
+    match rules.teams {
+        Teams::On { friendly_fire: false, stocks: TeamStocks::Individual } => { }
+        _ => panic!("teams should be on with individual stocks")
+    }
+}
+
+#[test]
+fn upgrade_rules_teams_off() {
+    let mut json = rules21();
+    json["teams"] = json!("Off");
+    upgrade_to_latest_rules(&mut json);
+    let rules: Rules = serde_json::from_value(json).unwrap();
+
+    match rules.teams {
+        Teams::Off => { }
+        _ => panic!("teams should stay off")
+    }
+}
+
+#[test]
+fn upgrade_rules_latest() {
+    let latest = serde_json::to_value(Rules::default()).unwrap();
+    let mut json = latest.clone();
+    upgrade_to_latest_rules(&mut json);
+    assert_eq!(json, latest);
+
+    // rules from a newer version are left alone
+    let mut newer = latest.clone();
+    newer["engine_version"] = json!(engine_version() + 1);
+    let mut json = newer.clone();
+    upgrade_to_latest_rules(&mut json);
+    assert_eq!(json, newer);
+}
+
+/// The actions of a fighter at engine version 15, in order
+const ACTIONS15: [&str; 88] = [
+    "Spawn", "ReSpawn", "ReSpawnIdle", "Idle", "Crouch", "LedgeIdle", "Teeter", "TeeterIdle", "MissedTechIdle", "Fall",
+    "AerialFall", "Land", "JumpSquat", "JumpF", "JumpB", "JumpAerialF", "JumpAerialB", "TiltTurn", "RunTurn",
+    "SmashTurn", "Dash", "Run", "RunEnd", "Walk", "PassPlatform", "Damage", "DamageFly", "DamageFall", "LedgeGrab",
+    "LedgeJump", "LedgeJumpSlow", "LedgeGetup", "LedgeGetupSlow", "PowerShield", "ShieldOn", "Shield", "ShieldOff",
+    "RollF", "RollB", "SpotDodge", "AerialDodge", "SpecialFall", "SpecialLand", "TechF", "TechN", "TechB",
+    "MissedTechGetupF", "MissedTechGetupN", "MissedTechGetupB", "Rebound", "LedgeRoll", "LedgeRollSlow",
+    "ShieldBreakFall", "ShieldBreakGetup", "Stun", "MissedTechStart", "Jab", "Jab2", "Jab3", "Utilt", "Dtilt", "Ftilt",
+    "DashAttack", "Usmash", "Dsmash", "Fsmash", "Grab", "DashGrab", "LedgeAttack", "LedgeAttackSlow",
+    "MissedTechAttack", "Uair", "Dair", "Fair", "Bair", "Nair", "UairLand", "DairLand", "FairLand", "BairLand",
+    "NairLand", "TauntUp", "TauntDown", "TauntLeft", "TauntRight", "CrouchStart", "CrouchEnd", "Eliminated"
+];
+
+/// A fighter at engine version 15, each action stores its index in iasa so it can be found after the upgrade
+fn fighter15() -> Value {
+    let actions: Vec<Value> = (0..ACTIONS15.len()).map(|i| json!({ "frames": [{ "colboxes": [] }], "iasa": i })).collect();
+    json!({ "engine_version": 15, "actions": actions, "projectiles": [] })
+}
+
+fn action_index(name: &str) -> usize {
+    Action::iter().position(|action| format!("{:?}", action) == name).unwrap()
+}
+
+#[test]
+fn upgrade_fighter_actions() {
+    let mut fighter = fighter15();
+    upgrade_to_latest_fighter(&mut fighter, "test");
+    let actions = fighter["actions"].as_array().unwrap();
+    assert_eq!(actions.len(), Action::DummyFramePreStart as usize);
+
+    // existing actions are moved to their new index
+    for (i, name) in ACTIONS15.iter().enumerate() {
+        assert_eq!(actions[action_index(name)]["iasa"], json!(i), "{}", name);
+    }
+    assert_eq!(actions[Action::Uair as usize]["iasa"], json!(71));
+    assert_eq!(actions[Action::Dair as usize]["iasa"], json!(72));
+
+    // grab actions
+    let hit = |action: Action| actions[action as usize]["frames"][0]["colboxes"][0]["role"]["Hit"].clone();
+    for action in [Action::GrabbingIdle, Action::GrabbingEnd, Action::GrabbedIdle, Action::GrabbedEnd].iter() {
+        assert_eq!(actions[action.clone() as usize]["frames"][0]["colboxes"], json!([]));
+    }
+    assert_eq!(hit(Action::Pummel)["damage"], json!(3.0));
+    assert_eq!(hit(Action::ThrowF)["angle"],  json!(45.0));
+    assert_eq!(hit(Action::ThrowB)["angle"],  json!(135.0));
+    assert_eq!(hit(Action::ThrowU)["angle"],  json!(90.0));
+    assert_eq!(hit(Action::ThrowD)["angle"],  json!(80.0));
+
+    // special actions, only the up specials can grab the ledge
+    let frame = |action: Action| actions[action as usize]["frames"][0].clone();
+    assert_eq!(actions[Action::TauntUp as usize]["iasa"],   json!(81));
+    assert_eq!(actions[Action::TauntDown as usize]["iasa"], json!(82));
+    assert_eq!(frame(Action::NspecialGround)["pass_through"], json!(true));
+    assert_eq!(frame(Action::NspecialAir)["pass_through"],    json!(false));
+    assert_eq!(frame(Action::NspecialGround)["ledge_grab_box"], Value::Null);
+    assert_eq!(frame(Action::DspecialGround)["ledge_grab_box"], Value::Null);
+    assert_eq!(frame(Action::DspecialAir)["ledge_grab_box"],    Value::Null);
+    assert!(frame(Action::UspecialGround)["ledge_grab_box"].is_object());
+    assert!(frame(Action::UspecialAir)["ledge_grab_box"].is_object());
+    assert_eq!(frame(Action::UspecialAir)["pass_through"], json!(false));
+}
@@ -0,0 +1,62 @@
+use pf_sandbox_lib::stage::{Stage, SpawnPoint};
+use treeflection::ContextVec;
+
+fn stage(points: Vec<SpawnPoint>) -> Stage {
+    Stage {
+        spawn_points:   ContextVec::from_vec(points.clone()),
+        respawn_points: ContextVec::from_vec(points),
+        .. Stage::default()
+    }
+}
+
+fn position(point: Option<SpawnPoint>) -> (f32, f32) {
+    let point = point.unwrap();
+    (point.x, point.y)
+}
+
+#[test]
+fn spawn_point_none() {
+    let stage = stage(vec!());
+    assert!(stage.spawn_point(0).is_none());
+    assert!(stage.respawn_point(5).is_none());
+}
+
+#[test]
+fn spawn_point_cycle() {
+    let stage = stage(vec!(SpawnPoint::new(0.0, 0.0), SpawnPoint::new(100.0, 0.0), SpawnPoint::new(100.0, 100.0)));
+
+    // a point for each player
+    assert_eq!(position(stage.spawn_point(0)), (0.0, 0.0));
+    assert_eq!(position(stage.spawn_point(1)), (100.0, 0.0));
+    assert_eq!(position(stage.spawn_point(2)), (100.0, 100.0));
+
+    // extra players are placed towards the next point
+    assert_eq!(position(stage.spawn_point(3)), (25.0, 0.0));
+    assert_eq!(position(stage.spawn_point(4)), (100.0, 25.0));
+    assert_eq!(position(stage.spawn_point(5)), (75.0, 75.0));
+
+    assert_eq!(position(stage.respawn_point(3)), (25.0, 0.0));
+}
+
+#[test]
+fn spawn_point_never_shared() {
+    for count in 2..5 {
+        let points = (0..count).map(|i| SpawnPoint::new(i as f32 * 10.0, (i % 2) as f32 * 10.0)).collect();
+        let stage = stage(points);
+
+        let positions: Vec<(f32, f32)> = (0..16).map(|i| position(stage.spawn_point(i))).collect();
+        for (i, a) in positions.iter().enumerate() {
+            for b in &positions[i + 1 ..] {
+                assert_ne!(a, b, "{} points", count);
+            }
+        }
+    }
+}
+
+#[test]
+fn spawn_point_single() {
+    let stage = stage(vec!(SpawnPoint::new(5.0, 10.0)));
+    for i in 0..4 {
+        assert_eq!(position(stage.spawn_point(i)), (5.0, 10.0));
+    }
+}